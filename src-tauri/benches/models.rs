@@ -0,0 +1,219 @@
+//! Benchmarks of each stage of the models: preprocessing, inference and postprocessing, at
+//! several image sizes and thread counts.
+//!
+//! Set `IMAGENIE_MODELS_DIR` to a directory with downloaded models to measure the real ones;
+//! models missing from it are replaced by the synthetic graphs of the integration tests.
+//!
+//! After the timings of each model, the peak heap use of every stage is printed, with the peak
+//! of inference when the input is cloned before the run as it used to be, and the saving. Memory
+//! that ONNX Runtime allocates itself is not counted.
+
+#[allow(dead_code)]
+#[path = "../tests/common/onnx.rs"]
+mod onnx;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, RgbImage};
+use imagenie_lib::image::model::{BackgroundRemovalModel, FaceRestorationModel, UpscalingModel};
+use imagenie_lib::image::session::{build_session, SessionConfig};
+use imagenie_lib::image::types::{BackgroundRemovalParams, FaceRestorationParams, UpscalingParams};
+use imagenie_lib::image::ImageModel;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SIZES: [u32; 3] = [128, 256, 512];
+const THREADS: [usize; 3] = [1, 2, 4];
+
+/// The system allocator, keeping track of the bytes in use and their peak.
+struct PeakAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+impl PeakAllocator {
+    fn grow(size: usize) {
+        let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            Self::grow(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Runs `f`, returning its result and the most heap it used on top of what was already in use.
+fn peak_heap<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let base = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed).saturating_sub(base))
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Uses `file_name` from `IMAGENIE_MODELS_DIR` if it is there, else writes `synthetic` to `dir`.
+fn model_path(dir: &Path, file_name: &str, synthetic: fn() -> Vec<u8>) -> String {
+    let real =
+        std::env::var_os("IMAGENIE_MODELS_DIR").map(|models| PathBuf::from(models).join(file_name));
+    let path = match real {
+        Some(path) if path.exists() => path,
+        _ => {
+            let path = dir.join(file_name);
+            std::fs::write(&path, synthetic()).unwrap();
+            path
+        }
+    };
+    path.to_str().unwrap().to_string()
+}
+
+fn write_input(dir: &Path, size: u32) -> String {
+    let image = RgbImage::from_fn(size, size, |x, y| {
+        image::Rgb([
+            (x * 255 / size) as u8,
+            (y * 255 / size) as u8,
+            ((x ^ y) % 256) as u8,
+        ])
+    });
+    let path = dir.join(format!("{}.png", size));
+    DynamicImage::ImageRgb8(image).save(&path).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn bench_model<M: ImageModel>(c: &mut Criterion, name: &str, model_path: &str, params: M::Params)
+where
+    M::Input: Clone,
+{
+    let dir = tempfile::tempdir().unwrap();
+    let inputs: Vec<(u32, String)> = SIZES
+        .iter()
+        .map(|&size| (size, write_input(dir.path(), size)))
+        .collect();
+
+    let mut group = c.benchmark_group(format!("{}/preprocess", name));
+    for (size, path) in &inputs {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), path, |b, path| {
+            b.iter(|| M::preprocess(path, &mut params.clone()).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group(format!("{}/process", name));
+    group.sample_size(10);
+    for threads in THREADS {
+        let config = SessionConfig {
+            intra_threads: threads,
+            ..Default::default()
+        };
+        let session = build_session(model_path, &config).unwrap();
+        for (size, path) in &inputs {
+            group.throughput(Throughput::Elements((size * size) as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_threads", threads), size),
+                path,
+                |b, path| {
+                    // The model takes its input by value, so each run gets a fresh one
+                    b.iter_batched(
+                        || M::preprocess(path, &mut params.clone()).unwrap(),
+                        |input| M::process(&session, input).unwrap(),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+
+    let session = build_session(model_path, &SessionConfig::default()).unwrap();
+    let mut group = c.benchmark_group(format!("{}/postprocess", name));
+    for (size, path) in &inputs {
+        let mut params = params.clone();
+        let input = M::preprocess(path, &mut params).unwrap();
+        let output = M::process(&session, input).unwrap();
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &output, |b, output| {
+            b.iter(|| M::postprocess(output, &params).unwrap())
+        });
+    }
+    group.finish();
+
+    for (size, path) in &inputs {
+        let mut params = params.clone();
+        let (input, preprocess) = peak_heap(|| M::preprocess(path, &mut params).unwrap());
+        // The caller kept its input while the model ran on a copy
+        let (_, cloned_process) = peak_heap(|| M::process(&session, input.clone()).unwrap());
+        let (output, process) = peak_heap(|| M::process(&session, input).unwrap());
+        let (_, postprocess) = peak_heap(|| M::postprocess(&output, &params).unwrap());
+        println!(
+            "{}/{}: peak heap {:.1} MiB preprocess, {:.1} MiB process ({:.1} MiB with a cloned \
+             input, {:.1} MiB saved), {:.1} MiB postprocess",
+            name,
+            size,
+            mib(preprocess),
+            mib(process),
+            mib(cloned_process),
+            mib(cloned_process.saturating_sub(process)),
+            mib(postprocess)
+        );
+    }
+}
+
+fn upscaling(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let model = model_path(dir.path(), "image_upscaling.onnx", onnx::nearest_upscale_2x);
+    bench_model::<UpscalingModel>(c, "upscaling", &model, UpscalingParams::default());
+}
+
+fn face_restoration(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let model = model_path(dir.path(), "face_restoration.onnx", || {
+        onnx::identity(onnx::FLOAT)
+    });
+    bench_model::<FaceRestorationModel>(
+        c,
+        "face_restoration",
+        &model,
+        FaceRestorationParams::default(),
+    );
+}
+
+fn background_removal(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let model = model_path(dir.path(), "background_removal.onnx", onnx::brightness_mask);
+    let params = BackgroundRemovalParams {
+        model_width: 1024,
+        model_height: 1024,
+        composite: true,
+        ..Default::default()
+    };
+    bench_model::<BackgroundRemovalModel>(c, "background_removal", &model, params);
+}
+
+criterion_group!(benches, upscaling, face_restoration, background_removal);
+criterion_main!(benches);
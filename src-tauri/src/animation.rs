@@ -0,0 +1,604 @@
+//! Frame-by-frame processing of animated GIF, APNG and WebP images.
+//!
+//! Models only ever see single frames: every frame is written out as a still, run through the
+//! operation and read back, then the results are re-encoded with the original delays and loop
+//! count.
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::{AnimationDecoder, Delay, ImageDecoder, ImageFormat, ImageReader, RgbaImage};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::processor::{Progress, ProgressFn};
+use crate::image::validate::{
+    decoder_limits, estimate_memory, input_limits, validate, InputLimits, ValidationError,
+};
+use crate::image::ImageProcessingError;
+use crate::output::with_intermediate_format;
+use crate::utils::cache_dir;
+
+const ANIMATION_DIR: &str = "animation";
+/// Largest per-channel difference between frames for a pixel to count as static.
+const STATIC_THRESHOLD: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    WebP,
+}
+
+impl AnimationFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+            AnimationFormat::WebP => "webp",
+        }
+    }
+}
+
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    pub delay_ms: u32,
+}
+
+pub struct Animation {
+    pub format: AnimationFormat,
+    pub frames: Vec<AnimationFrame>,
+    /// Loop count as stored by the format, or `None` to loop forever.
+    pub loop_count: Option<u16>,
+}
+
+/// Returns the animation format of `path` if it holds more than one frame.
+pub fn detect(path: &str) -> Option<AnimationFormat> {
+    let format = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .format()?;
+    let reader = || File::open(path).ok().map(BufReader::new);
+
+    match format {
+        ImageFormat::Gif => {
+            let mut decoder = gif::DecodeOptions::new().read_info(reader()?).ok()?;
+            decoder.next_frame_info().ok()??;
+            decoder
+                .next_frame_info()
+                .ok()?
+                .map(|_| AnimationFormat::Gif)
+        }
+        ImageFormat::Png => PngDecoder::new(reader()?)
+            .ok()?
+            .is_apng()
+            .ok()?
+            .then_some(AnimationFormat::Apng),
+        ImageFormat::WebP => WebPDecoder::new(reader()?)
+            .ok()?
+            .has_animation()
+            .then_some(AnimationFormat::WebP),
+        _ => None,
+    }
+}
+
+fn delay_ms(delay: Delay) -> u32 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    numerator / denominator.max(1)
+}
+
+/// Rejects animations whose frames don't fit the input limits once decoded. All frames of the
+/// input and of the result are held in memory at once, next to the one frame being processed.
+fn check_frame_limits(
+    frames: u64,
+    (width, height): (u32, u32),
+    limits: &InputLimits,
+) -> Result<(), ValidationError> {
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_pixels {
+        return Err(ValidationError::TooManyPixels {
+            width,
+            height,
+            max_pixels: limits.max_pixels,
+        });
+    }
+
+    let required_bytes = frames
+        .saturating_mul(pixels)
+        .saturating_mul(2 * 4)
+        .saturating_add(estimate_memory(width, height, 1));
+    if required_bytes > limits.max_memory_bytes {
+        return Err(ValidationError::InsufficientMemory {
+            required_bytes,
+            max_bytes: limits.max_memory_bytes,
+        });
+    }
+    Ok(())
+}
+
+/// Decodes all frames of an animated image, composited to the full canvas, after checking that
+/// they fit the input limits.
+pub fn decode(path: &str, format: AnimationFormat) -> Result<Animation, ImageProcessingError> {
+    let reader = || File::open(path).map(BufReader::new);
+    let corrupt = |reason: String| ValidationError::Corrupt {
+        path: path.to_string(),
+        reason,
+    };
+    let limits = input_limits();
+
+    let (frames, loop_count) = match format {
+        AnimationFormat::Gif => {
+            let mut decoder = gif::DecodeOptions::new()
+                .read_info(reader()?)
+                .map_err(|e| corrupt(e.to_string()))?;
+            let canvas = (decoder.width() as u32, decoder.height() as u32);
+            // The loop count extension precedes the first frame; counting the frames only reads
+            // their headers
+            let mut count = 0;
+            while decoder
+                .next_frame_info()
+                .map_err(|e| corrupt(e.to_string()))?
+                .is_some()
+            {
+                count += 1;
+            }
+            check_frame_limits(count, canvas, &limits)?;
+            let loop_count = match decoder.repeat() {
+                gif::Repeat::Infinite => None,
+                gif::Repeat::Finite(count) => Some(count),
+            };
+
+            let mut decoder = GifDecoder::new(reader()?)?;
+            decoder.set_limits(decoder_limits())?;
+            (decoder.into_frames().collect_frames(), loop_count)
+        }
+        AnimationFormat::Apng => {
+            let png_reader = png::Decoder::new(reader()?)
+                .read_info()
+                .map_err(|e| corrupt(e.to_string()))?;
+            let info = png_reader.info();
+            let control = info.animation_control();
+            let count = control.map_or(1, |control| control.num_frames as u64);
+            check_frame_limits(count, (info.width, info.height), &limits)?;
+            let loop_count = control
+                .map(|control| control.num_plays.min(u16::MAX as u32) as u16)
+                .filter(|&plays| plays > 0);
+
+            let frames = PngDecoder::with_limits(reader()?, decoder_limits())?
+                .apng()?
+                .into_frames();
+            (frames.collect_frames(), loop_count)
+        }
+        AnimationFormat::WebP => {
+            let decoder =
+                image_webp::WebPDecoder::new(reader()?).map_err(|e| corrupt(e.to_string()))?;
+            check_frame_limits(decoder.num_frames() as u64, decoder.dimensions(), &limits)?;
+            let loop_count = match decoder.loop_count() {
+                image_webp::LoopCount::Forever => None,
+                image_webp::LoopCount::Times(count) => Some(count.get()),
+            };
+
+            let mut decoder = WebPDecoder::new(reader()?)?;
+            decoder.set_limits(decoder_limits())?;
+            (decoder.into_frames().collect_frames(), loop_count)
+        }
+    };
+
+    let frames: Vec<AnimationFrame> = frames?
+        .into_iter()
+        .map(|frame| AnimationFrame {
+            delay_ms: delay_ms(frame.delay()),
+            image: frame.into_buffer(),
+        })
+        .collect();
+    if frames.is_empty() {
+        return Err(corrupt("the animation has no frames".to_string()).into());
+    }
+
+    Ok(Animation {
+        format,
+        frames,
+        loop_count,
+    })
+}
+
+/// Encodes `animation` into `path` in its own format.
+pub fn encode(animation: &Animation, path: &Path) -> Result<(), ImageProcessingError> {
+    let Some(first) = animation.frames.first() else {
+        return Err(ImageProcessingError::Processing(
+            "The animation has no frames".to_string(),
+        ));
+    };
+    let file = BufWriter::new(File::create(path)?);
+
+    match animation.format {
+        AnimationFormat::Gif => encode_gif(animation, file),
+        AnimationFormat::Apng => encode_apng(animation, first.image.dimensions(), file),
+        AnimationFormat::WebP => encode_webp(animation, first.image.dimensions(), file),
+    }
+}
+
+fn encode_gif(animation: &Animation, writer: impl Write) -> Result<(), ImageProcessingError> {
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    let repeat = match animation.loop_count {
+        None => Repeat::Infinite,
+        Some(count) => Repeat::Finite(count),
+    };
+    encoder.set_repeat(repeat)?;
+
+    let frames = animation.frames.iter().map(|frame| {
+        image::Frame::from_parts(
+            frame.image.clone(),
+            0,
+            0,
+            Delay::from_numer_denom_ms(frame.delay_ms, 1),
+        )
+    });
+    Ok(encoder.encode_frames(frames)?)
+}
+
+fn png_error(error: png::EncodingError) -> ImageProcessingError {
+    ImageProcessingError::Processing(format!("Failed to encode APNG: {}", error))
+}
+
+fn encode_apng(
+    animation: &Animation,
+    (width, height): (u32, u32),
+    writer: impl Write,
+) -> Result<(), ImageProcessingError> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(
+            animation.frames.len() as u32,
+            animation.loop_count.unwrap_or(0) as u32,
+        )
+        .map_err(png_error)?;
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    for frame in &animation.frames {
+        let delay = frame.delay_ms.min(u16::MAX as u32) as u16;
+        writer.set_frame_delay(delay, 1000).map_err(png_error)?;
+        writer
+            .write_image_data(frame.image.as_raw())
+            .map_err(png_error)?;
+    }
+    writer.finish().map_err(png_error)
+}
+
+/// Writes an animated WebP by wrapping losslessly encoded frames in `ANMF` chunks, since the
+/// bundled encoder only produces still images.
+fn encode_webp(
+    animation: &Animation,
+    (width, height): (u32, u32),
+    mut writer: impl Write,
+) -> Result<(), ImageProcessingError> {
+    let mut chunks = Vec::new();
+    // Animation and alpha flags, then the canvas size minus one
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0];
+    vp8x.extend_from_slice(&u24(width - 1));
+    vp8x.extend_from_slice(&u24(height - 1));
+    write_chunk(&mut chunks, b"VP8X", &vp8x);
+
+    // Transparent background, then the loop count
+    let mut anim = vec![0, 0, 0, 0];
+    anim.extend_from_slice(&animation.loop_count.unwrap_or(0).to_le_bytes());
+    write_chunk(&mut chunks, b"ANIM", &anim);
+
+    for frame in &animation.frames {
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still).encode(
+            frame.image.as_raw(),
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )?;
+        let bitstream = find_chunk(&still, b"VP8L").ok_or_else(|| {
+            ImageProcessingError::Processing("Failed to encode WebP frame".to_string())
+        })?;
+
+        // Offset, size minus one, duration, then "do not blend" so every frame replaces the last
+        let mut anmf = vec![0; 6];
+        anmf.extend_from_slice(&u24(width - 1));
+        anmf.extend_from_slice(&u24(height - 1));
+        anmf.extend_from_slice(&u24(frame.delay_ms.min(0xFF_FFFF)));
+        anmf.push(0x02);
+        write_chunk(&mut anmf, b"VP8L", bitstream);
+        write_chunk(&mut chunks, b"ANMF", &anmf);
+    }
+
+    let size = 4 + chunks.len() as u32;
+    writer
+        .write_all(b"RIFF")
+        .and_then(|_| writer.write_all(&size.to_le_bytes()))
+        .and_then(|_| writer.write_all(b"WEBP"))
+        .and_then(|_| writer.write_all(&chunks))
+        .and_then(|_| writer.flush())?;
+    Ok(())
+}
+
+fn u24(value: u32) -> [u8; 3] {
+    let bytes = value.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+fn write_chunk(buffer: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    buffer.extend_from_slice(name);
+    buffer.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        buffer.push(0);
+    }
+}
+
+/// Returns the payload of the first `name` chunk of a RIFF WebP file.
+fn find_chunk<'a>(webp: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut offset = 12;
+    while offset + 8 <= webp.len() {
+        let size = u32::from_le_bytes(webp[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let data = webp.get(offset + 8..offset + 8 + size)?;
+        if &webp[offset..offset + 4] == name {
+            return Some(data);
+        }
+        offset += 8 + size + size % 2;
+    }
+    None
+}
+
+/// Smooths the alpha of processed frames over time where the input didn't change, so masks
+/// predicted independently per frame don't flicker on static areas.
+fn stabilize_alpha(inputs: &[RgbaImage], outputs: &mut [RgbaImage]) {
+    if outputs.len() < 3
+        || inputs
+            .iter()
+            .zip(outputs.iter())
+            .any(|(input, output)| input.dimensions() != output.dimensions())
+    {
+        return;
+    }
+
+    let alphas: Vec<Vec<u8>> = outputs
+        .iter()
+        .map(|output| output.pixels().map(|p| p[3]).collect())
+        .collect();
+    let last = outputs.len() - 1;
+
+    for (i, output) in outputs.iter_mut().enumerate() {
+        let (previous, next) = (i.saturating_sub(1), (i + 1).min(last));
+        let neighbors = [&inputs[previous], &inputs[next]];
+
+        for (index, (x, y, pixel)) in output.enumerate_pixels_mut().enumerate() {
+            let current = inputs[i].get_pixel(x, y);
+            let is_static = neighbors.iter().all(|neighbor| {
+                let other = neighbor.get_pixel(x, y);
+                (0..3).all(|c| current[c].abs_diff(other[c]) <= STATIC_THRESHOLD)
+            });
+            if is_static {
+                let sum = alphas[previous][index] as u32
+                    + 2 * alphas[i][index] as u32
+                    + alphas[next][index] as u32;
+                pixel[3] = (sum / 4) as u8;
+            }
+        }
+    }
+}
+
+/// Runs `operation` on `input_path`, frame by frame if it is animated.
+///
+/// `operation` receives a still image path and writes its result into the given directory, as
+/// the regular single-image functions do. Animated results keep the input's format and are
+/// named like the operation names its still outputs.
+pub(crate) fn run<F>(
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+    operation: F,
+) -> Result<String, CommandError>
+where
+    F: Fn(&str, &str, &ProgressFn) -> Result<String, CommandError>,
+{
+    let Some(format) = detect(input_path) else {
+        return operation(input_path, output_dir, progress);
+    };
+
+    let work_dir = cache_dir()
+        .join(ANIMATION_DIR)
+        .join(uuid::Uuid::new_v4().to_string());
+    let result = run_frames(
+        input_path, output_dir, format, &work_dir, progress, operation,
+    );
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+fn run_frames<F>(
+    input_path: &str,
+    output_dir: &str,
+    format: AnimationFormat,
+    work_dir: &Path,
+    progress: &ProgressFn,
+    operation: F,
+) -> Result<String, CommandError>
+where
+    F: Fn(&str, &str, &ProgressFn) -> Result<String, CommandError>,
+{
+    validate(input_path, 1)?;
+    let mut animation = decode(input_path, format)?;
+    info!(
+        "Processing {} frames of animated {}",
+        animation.frames.len(),
+        input_path
+    );
+
+    let frames_dir = work_dir.join("frames");
+    let processed_dir = work_dir.join("processed");
+    std::fs::create_dir_all(&frames_dir)?;
+    std::fs::create_dir_all(&processed_dir)?;
+
+    // Frames are named after the input so the operation's output name carries over
+    let stem = Path::new(input_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let frame_path = frames_dir.join(format!("{}.png", stem));
+    let frame_path = frame_path.to_str().unwrap();
+
+    let total = animation.frames.len() as f32;
+    let mut output_stem = None;
+    let mut processed = Vec::with_capacity(animation.frames.len());
+    for (index, frame) in animation.frames.iter().enumerate() {
+        frame.image.save(frame_path)?;
+
+        let output = with_intermediate_format(|| {
+            operation(frame_path, processed_dir.to_str().unwrap(), &|step| {
+                progress(Progress {
+                    stage: step.stage,
+                    fraction: (index as f32 + step.fraction) / total,
+                })
+            })
+        })?;
+
+        processed.push(image::open(&output)?.into_rgba8());
+        output_stem.get_or_insert_with(|| {
+            Path::new(&output)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        std::fs::remove_file(&output)?;
+    }
+
+    let inputs: Vec<RgbaImage> = animation
+        .frames
+        .iter_mut()
+        .map(|frame| std::mem::take(&mut frame.image))
+        .collect();
+    stabilize_alpha(&inputs, &mut processed);
+    for (frame, image) in animation.frames.iter_mut().zip(processed) {
+        frame.image = image;
+    }
+
+    let output_path = Path::new(output_dir).join(format!(
+        "{}.{}",
+        output_stem.unwrap_or(stem),
+        format.extension()
+    ));
+    encode(&animation, &output_path)?;
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELAYS_MS: [u32; 3] = [100, 50, 200];
+
+    fn animation(format: AnimationFormat, loop_count: Option<u16>) -> Animation {
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let frames = colors
+            .into_iter()
+            .zip(DELAYS_MS)
+            .map(|(color, delay_ms)| AnimationFrame {
+                image: RgbaImage::from_pixel(8, 6, image::Rgba(color)),
+                delay_ms,
+            })
+            .collect();
+        Animation {
+            format,
+            frames,
+            loop_count,
+        }
+    }
+
+    fn round_trip(format: AnimationFormat, loop_count: Option<u16>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("animation.{}", format.extension()));
+        let original = animation(format, loop_count);
+        encode(&original, &path).unwrap();
+
+        let path = path.to_str().unwrap();
+        assert_eq!(detect(path), Some(format));
+        let decoded = decode(path, format).unwrap();
+        assert_eq!(decoded.format, format);
+        assert_eq!(decoded.loop_count, loop_count);
+        assert_eq!(decoded.frames.len(), original.frames.len());
+        for (decoded, original) in decoded.frames.iter().zip(&original.frames) {
+            assert_eq!(decoded.delay_ms, original.delay_ms);
+            assert_eq!(decoded.image.dimensions(), original.image.dimensions());
+            assert_eq!(
+                decoded.image.get_pixel(3, 2),
+                original.image.get_pixel(3, 2)
+            );
+        }
+    }
+
+    #[test]
+    fn gif_round_trips() {
+        round_trip(AnimationFormat::Gif, Some(3));
+        round_trip(AnimationFormat::Gif, None);
+    }
+
+    #[test]
+    fn apng_round_trips() {
+        round_trip(AnimationFormat::Apng, Some(3));
+        round_trip(AnimationFormat::Apng, None);
+    }
+
+    #[test]
+    fn webp_round_trips() {
+        round_trip(AnimationFormat::WebP, Some(3));
+        round_trip(AnimationFormat::WebP, None);
+    }
+
+    #[test]
+    fn animations_over_the_limits_are_rejected() {
+        let limits = InputLimits {
+            max_pixels: 100,
+            max_memory_bytes: 1_000_000,
+            ..Default::default()
+        };
+
+        assert!(check_frame_limits(10, (10, 10), &limits).is_ok());
+        assert!(matches!(
+            check_frame_limits(1, (11, 10), &limits),
+            Err(ValidationError::TooManyPixels {
+                width: 11,
+                height: 10,
+                ..
+            })
+        ));
+        // Each frame fits, but not all of them at once
+        assert!(matches!(
+            check_frame_limits(2_000, (10, 10), &limits),
+            Err(ValidationError::InsufficientMemory { .. })
+        ));
+    }
+
+    #[test]
+    fn animations_without_frames_are_not_encoded() {
+        let dir = tempfile::tempdir().unwrap();
+        let animation = Animation {
+            format: AnimationFormat::WebP,
+            frames: Vec::new(),
+            loop_count: None,
+        };
+
+        assert!(encode(&animation, &dir.path().join("empty.webp")).is_err());
+    }
+
+    #[test]
+    fn stills_are_not_detected_as_animations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("still.png");
+        RgbaImage::new(4, 4).save(&path).unwrap();
+        assert_eq!(detect(path.to_str().unwrap()), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! Quick on-device benchmark of the downloaded models, for choosing how many threads a session
+//! uses. Models process whole images, so there is no tile size to tune here.
+
+use image::{DynamicImage, RgbImage};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::info;
+
+use crate::commands::{
+    artifact_removal::ARTIFACT_REMOVAL_MODEL, background_removal::BACKGROUND_REMOVAL_MODEL,
+    colorization::COLORIZATION_MODEL, denoise::DENOISE_MODEL, enhance::ENHANCE_MODEL,
+    face_restoration::FACE_RESTORATION_MODEL, segmentation::SEGMENTATION_MODEL,
+    upscaling::UPSCALE_MODEL,
+};
+use crate::error::CommandError;
+use crate::image::manager::{ManagedModel, ModelSlot};
+use crate::image::processor::{
+    ModelProcessor, ProcessingStage, Progress, ProgressFn, StageTimings,
+};
+use crate::image::session::{session_config, SessionConfig};
+use crate::image::types::{
+    ArtifactRemovalParams, BackgroundRemovalParams, ColorizationParams, DenoiseParams,
+    EnhanceParams, FaceRestorationParams, SegmentationParams, UpscalingParams,
+};
+use crate::image::ImageModel;
+use crate::utils::cache_dir;
+
+const BENCHMARK_DIR: &str = "benchmark";
+/// Input sizes (square) measured when none are given; small enough to finish in seconds.
+pub const DEFAULT_SIZES: [u32; 2] = [128, 256];
+/// Thread counts within this share of the fastest count as equally fast, so fewer threads win.
+const THREADS_TOLERANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRun {
+    pub threads: usize,
+    /// Width and height of the input.
+    pub size: u32,
+    #[serde(flatten)]
+    pub timings: StageTimings,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelBenchmark {
+    pub model: &'static str,
+    pub runs: Vec<BenchmarkRun>,
+    pub recommended_threads: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub cores: usize,
+    pub models: Vec<ModelBenchmark>,
+    /// Requested models that were skipped because they are not downloaded.
+    pub missing: Vec<String>,
+    pub recommended_threads: Option<usize>,
+    /// How many jobs can run at once with the recommended threads without oversubscribing.
+    pub recommended_concurrent_jobs: Option<usize>,
+}
+
+struct Plan<'a> {
+    inputs: Vec<(u32, String)>,
+    threads: Vec<usize>,
+    base_config: SessionConfig,
+    total_steps: usize,
+    done: AtomicUsize,
+    progress: &'a ProgressFn<'a>,
+}
+
+impl Plan<'_> {
+    fn step(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        (self.progress)(Progress {
+            stage: ProcessingStage::Inference,
+            fraction: done as f32 / self.total_steps.max(1) as f32,
+        });
+    }
+}
+
+/// Powers of two up to the core count, and the core count itself.
+fn thread_candidates(cores: usize) -> Vec<usize> {
+    let mut threads: Vec<usize> = std::iter::successors(Some(1), |&threads| Some(threads * 2))
+        .take_while(|&threads| threads < cores)
+        .collect();
+    threads.push(cores);
+    threads
+}
+
+/// Fewest threads whose total inference time is within the tolerance of the fastest.
+fn fastest_threads<'a>(runs: impl Iterator<Item = &'a BenchmarkRun>) -> Option<usize> {
+    let mut totals: Vec<(usize, f64)> = Vec::new();
+    for run in runs {
+        match totals
+            .iter_mut()
+            .find(|(threads, _)| *threads == run.threads)
+        {
+            Some((_, total)) => *total += run.timings.inference_ms,
+            None => totals.push((run.threads, run.timings.inference_ms)),
+        }
+    }
+
+    let fastest = totals.iter().map(|(_, total)| *total).reduce(f64::min)?;
+    totals
+        .iter()
+        .filter(|(_, total)| *total <= fastest * (1.0 + THREADS_TOLERANCE))
+        .map(|(threads, _)| *threads)
+        .min()
+}
+
+fn benchmark_model<M: ImageModel + Send + Sync>(
+    slot: &ModelSlot<M>,
+    params: M::Params,
+    plan: &Plan,
+) -> Result<ModelBenchmark, CommandError> {
+    let model_path = slot.model_path();
+    let mut runs = Vec::new();
+
+    for &threads in &plan.threads {
+        let config = SessionConfig {
+            intra_threads: threads,
+            ..plan.base_config
+        };
+        let processor = ModelProcessor::<M>::with_config(model_path.to_str().unwrap(), &config)?;
+
+        // The first run pays for memory allocation and kernel selection
+        processor.process_single_timed(&plan.inputs[0].1, &params)?;
+        plan.step();
+
+        for (size, input_path) in &plan.inputs {
+            let (_, timings) = processor.process_single_timed(input_path, &params)?;
+            info!(
+                "Benchmark of {} with {} threads at {}px: {:?}",
+                slot.name(),
+                threads,
+                size,
+                timings
+            );
+            runs.push(BenchmarkRun {
+                threads,
+                size: *size,
+                timings,
+            });
+            plan.step();
+        }
+    }
+
+    let recommended_threads =
+        fastest_threads(runs.iter()).unwrap_or(plan.base_config.intra_threads);
+    Ok(ModelBenchmark {
+        model: slot.name(),
+        runs,
+        recommended_threads,
+    })
+}
+
+/// Gradients with some texture, so models do real work on it.
+fn write_input(dir: &Path, size: u32) -> Result<String, CommandError> {
+    let image = RgbImage::from_fn(size, size, |x, y| {
+        let texture = ((x * 7 + y * 13) % 32) as u8;
+        image::Rgb([
+            (x * 255 / size) as u8,
+            (y * 255 / size) as u8,
+            texture.wrapping_mul(8),
+        ])
+    });
+    let path = dir.join(format!("{}.png", size));
+    DynamicImage::ImageRgb8(image).save(&path)?;
+    Ok(path.to_str().unwrap().to_string())
+}
+
+/// Benchmarks the downloaded models among `models` (all by default) at each of `sizes`, with
+/// every candidate thread count.
+pub fn benchmark_models(
+    models: Option<Vec<String>>,
+    sizes: Option<Vec<u32>>,
+    progress: &ProgressFn,
+) -> Result<BenchmarkReport, CommandError> {
+    let selected = |name: &str| {
+        models
+            .as_ref()
+            .is_none_or(|models| models.iter().any(|model| model == name))
+    };
+    let available = |model: &dyn ManagedModel| selected(model.name()) && model.is_available();
+    let benchmarked: [&dyn ManagedModel; 8] = [
+        &UPSCALE_MODEL,
+        &DENOISE_MODEL,
+        &ARTIFACT_REMOVAL_MODEL,
+        &ENHANCE_MODEL,
+        &FACE_RESTORATION_MODEL,
+        &BACKGROUND_REMOVAL_MODEL,
+        &COLORIZATION_MODEL,
+        &SEGMENTATION_MODEL,
+    ];
+    let missing = benchmarked
+        .iter()
+        .filter(|model| selected(model.name()) && !model.is_available())
+        .map(|model| model.name().to_string())
+        .collect();
+    let count = benchmarked
+        .iter()
+        .filter(|model| available(**model))
+        .count();
+
+    let sizes = sizes
+        .filter(|sizes| !sizes.is_empty())
+        .unwrap_or(DEFAULT_SIZES.to_vec());
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = thread_candidates(cores);
+
+    let work_dir = cache_dir()
+        .join(BENCHMARK_DIR)
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&work_dir)?;
+    let result: Result<Vec<ModelBenchmark>, CommandError> = (|| {
+        let inputs = sizes
+            .iter()
+            .map(|&size| Ok((size, write_input(&work_dir, size)?)))
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        let plan = Plan {
+            total_steps: count * threads.len() * (inputs.len() + 1),
+            inputs,
+            threads,
+            base_config: session_config(),
+            done: AtomicUsize::new(0),
+            progress,
+        };
+
+        let mut benchmarks = Vec::new();
+        let mut run = |benchmark: fn(&Plan) -> Result<ModelBenchmark, CommandError>,
+                       model: &dyn ManagedModel| {
+            if available(model) {
+                benchmarks.push(benchmark(&plan)?);
+            }
+            Ok::<_, CommandError>(())
+        };
+        run(
+            |plan| benchmark_model(&UPSCALE_MODEL, UpscalingParams {}, plan),
+            &UPSCALE_MODEL,
+        )?;
+        run(
+            |plan| benchmark_model(&DENOISE_MODEL, DenoiseParams::default(), plan),
+            &DENOISE_MODEL,
+        )?;
+        run(
+            |plan| {
+                benchmark_model(
+                    &ARTIFACT_REMOVAL_MODEL,
+                    ArtifactRemovalParams::default(),
+                    plan,
+                )
+            },
+            &ARTIFACT_REMOVAL_MODEL,
+        )?;
+        run(
+            |plan| benchmark_model(&ENHANCE_MODEL, EnhanceParams::default(), plan),
+            &ENHANCE_MODEL,
+        )?;
+        run(
+            |plan| {
+                let params = FaceRestorationParams::default();
+                benchmark_model(&FACE_RESTORATION_MODEL, params, plan)
+            },
+            &FACE_RESTORATION_MODEL,
+        )?;
+        run(
+            |plan| {
+                let params = BackgroundRemovalParams {
+                    model_width: 1024,
+                    model_height: 1024,
+                    composite: true,
+                    ..Default::default()
+                };
+                benchmark_model(&BACKGROUND_REMOVAL_MODEL, params, plan)
+            },
+            &BACKGROUND_REMOVAL_MODEL,
+        )?;
+        run(
+            |plan| {
+                let params = ColorizationParams::default();
+                benchmark_model(&COLORIZATION_MODEL, params, plan)
+            },
+            &COLORIZATION_MODEL,
+        )?;
+        run(
+            |plan| {
+                let params = SegmentationParams::default();
+                benchmark_model(&SEGMENTATION_MODEL, params, plan)
+            },
+            &SEGMENTATION_MODEL,
+        )?;
+        Ok(benchmarks)
+    })();
+    let _ = std::fs::remove_dir_all(&work_dir);
+    let benchmarks = result?;
+
+    let recommended_threads = fastest_threads(
+        benchmarks
+            .iter()
+            .flat_map(|benchmark| benchmark.runs.iter()),
+    );
+    Ok(BenchmarkReport {
+        cores,
+        models: benchmarks,
+        missing,
+        recommended_threads,
+        recommended_concurrent_jobs: recommended_threads.map(|threads| (cores / threads).max(1)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(threads: usize, inference_ms: f64) -> BenchmarkRun {
+        BenchmarkRun {
+            threads,
+            size: 128,
+            timings: StageTimings {
+                inference_ms,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn thread_candidates_end_at_the_core_count() {
+        assert_eq!(thread_candidates(1), vec![1]);
+        assert_eq!(thread_candidates(4), vec![1, 2, 4]);
+        assert_eq!(thread_candidates(6), vec![1, 2, 4, 6]);
+    }
+
+    #[test]
+    fn fewer_threads_win_when_nearly_as_fast() {
+        let runs = [
+            run(1, 100.0),
+            run(2, 52.0),
+            run(4, 50.0),
+            run(2, 10.0),
+            run(4, 10.0),
+        ];
+        assert_eq!(fastest_threads(runs.iter()), Some(2));
+
+        let runs = [run(1, 100.0), run(2, 60.0), run(4, 50.0)];
+        assert_eq!(fastest_threads(runs.iter()), Some(4));
+        assert_eq!(fastest_threads([].iter()), None);
+    }
+}
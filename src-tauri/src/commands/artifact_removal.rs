@@ -0,0 +1,63 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::run_job;
+use crate::error::CommandError;
+use crate::image::{
+    manager::ModelSlot, model::ArtifactRemovalModel, processor::ProgressFn,
+    types::ArtifactRemovalParams,
+};
+use crate::output::{save_output, OutputFormat};
+
+pub(crate) static ARTIFACT_REMOVAL_MODEL: ModelSlot<ArtifactRemovalModel> =
+    ModelSlot::new("artifact_removal", "artifact_removal.onnx");
+
+#[tauri::command]
+pub async fn init_artifact_removal() -> Result<(), CommandError> {
+    ARTIFACT_REMOVAL_MODEL.get()?;
+    Ok(())
+}
+
+/// Removes compression artifacts and blur from `input_path` and writes the result into
+/// `output_dir`, returning the output path.
+pub(crate) fn remove_artifacts(
+    input_path: &str,
+    output_dir: &str,
+    quality: Option<f32>,
+    strength: Option<f32>,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = ARTIFACT_REMOVAL_MODEL.get()?;
+    let params = ArtifactRemovalParams {
+        quality,
+        strength: strength.unwrap_or(1.0).clamp(0.0, 1.0),
+        ..Default::default()
+    };
+
+    let image = processor.process_single_with_progress(input_path, &params, progress)?;
+
+    save_output(&image, input_path, output_dir, "cleaned")
+}
+
+#[tauri::command]
+pub async fn artifact_removal(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    quality: Option<f32>,
+    strength: Option<f32>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("artifact_removal was called with path: {}", input_path);
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        move |input, output, progress| remove_artifacts(input, output, quality, strength, progress),
+    )
+    .await
+}
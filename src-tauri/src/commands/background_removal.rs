@@ -1,92 +1,60 @@
-use crate::utils::models_dir;
-use image::DynamicImage;
-use std::path::Path;
-use std::sync::OnceLock;
+use crate::error::CommandError;
+use crate::output::{save_output, OutputFormat};
+use tauri::AppHandle;
 use tracing::info;
 
+use super::run_job;
 use crate::image::{
-    model::BackgroundRemovalModel, processor::ModelProcessor, types::BackgroundRemovalParams,
+    manager::ModelSlot, model::BackgroundRemovalModel, processor::ProgressFn,
+    types::BackgroundRemovalParams,
 };
 
-static BACKGROUND_REMOVAL_PROCESSOR: OnceLock<ModelProcessor<BackgroundRemovalModel>> =
-    OnceLock::new();
+pub(crate) static BACKGROUND_REMOVAL_MODEL: ModelSlot<BackgroundRemovalModel> =
+    ModelSlot::new("background_removal", "background_removal.onnx");
 
 #[tauri::command]
-pub async fn init_background_removal() -> Result<(), String> {
-    let models_dir = models_dir();
-    BACKGROUND_REMOVAL_PROCESSOR.get_or_init(|| {
-        let model_path = models_dir.join("background_removal.onnx");
-
-        ModelProcessor::<BackgroundRemovalModel>::new(model_path.to_str().unwrap())
-            .map_err(|e| e.to_string())
-            .unwrap()
-    });
+pub async fn init_background_removal() -> Result<(), CommandError> {
+    BACKGROUND_REMOVAL_MODEL.get()?;
     Ok(())
 }
 
-fn get_background_removal_processor() -> &'static ModelProcessor<BackgroundRemovalModel> {
-    BACKGROUND_REMOVAL_PROCESSOR.get().unwrap()
-}
-
-#[tauri::command]
-pub async fn background_removal(input_path: &str, output_dir: &str) -> Result<String, String> {
-    info!("background_removal was called with path: {}", input_path);
-
-    let processor = get_background_removal_processor();
+/// Removes the background of `input_path` and writes the result into `output_dir`, returning
+/// the output path.
+pub(crate) fn remove_background(
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = BACKGROUND_REMOVAL_MODEL.get()?;
+    // The model applies its mask to the image it decoded, so the input is only read once
     let params = BackgroundRemovalParams {
         model_width: 1024,
         model_height: 1024,
+        composite: true,
         ..Default::default()
     };
+    let final_image = processor.process_single_with_progress(input_path, &params, progress)?;
 
-    // Get the mask from model processing
-    let mask = processor
-        .process_single(input_path, &params)
-        .map_err(|e| e.to_string())?;
-
-    // Load original image
-    let original = image::open(input_path).map_err(|e| e.to_string())?;
-
-    // Ensure original image and mask have same dimensions
-    let mask = mask.resize_exact(
-        original.width(),
-        original.height(),
-        image::imageops::FilterType::Triangle,
-    );
-
-    // Create final image by combining original colors with mask
-    let mut final_image = image::RgbaImage::new(original.width(), original.height());
-    let original_rgba = original.to_rgba8();
-    let mask_rgba = mask.to_rgba8();
-
-    for (x, y, pixel) in final_image.enumerate_pixels_mut() {
-        let original_pixel = original_rgba.get_pixel(x, y);
-        let mask_pixel = mask_rgba.get_pixel(x, y);
-
-        *pixel = image::Rgba([
-            original_pixel[0], // R from original
-            original_pixel[1], // G from original
-            original_pixel[2], // B from original
-            mask_pixel[3],     // A from mask
-        ]);
-    }
-
-    // Save the result
-    let name = Path::new(input_path)
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .into_owned();
-    let name = name
-        .strip_suffix(".png")
-        .or_else(|| name.strip_suffix(".jpg"))
-        .or_else(|| name.strip_suffix(".jpeg"))
-        .unwrap_or(&name);
-    let output_path = Path::new(output_dir).join(format!("{}_removed.png", name));
+    save_output(&final_image, input_path, output_dir, "removed")
+}
 
-    DynamicImage::ImageRgba8(final_image)
-        .save(&output_path)
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn background_removal(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("background_removal was called with path: {}", input_path);
 
-    Ok(output_path.to_str().unwrap().to_string())
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        remove_background,
+    )
+    .await
 }
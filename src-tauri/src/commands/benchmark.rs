@@ -0,0 +1,68 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::progress::ProgressReporter;
+use crate::benchmark::{self, BenchmarkReport};
+use crate::error::CommandError;
+use crate::image::session::{self, SessionConfig};
+use crate::models::managed_models;
+use crate::worker::{pool, run_blocking};
+
+/// Measures the downloaded models at each input size in `sizes` with several thread counts and
+/// recommends settings. With `apply`, sessions switch to the recommended threads and the worker
+/// pool to the recommended number of concurrent jobs.
+#[tauri::command]
+pub async fn benchmark_models(
+    app: AppHandle,
+    models: Option<Vec<String>>,
+    sizes: Option<Vec<u32>>,
+    apply: Option<bool>,
+    job_id: Option<String>,
+) -> Result<BenchmarkReport, CommandError> {
+    info!(
+        "benchmark_models was called with models: {:?}, sizes: {:?}",
+        models, sizes
+    );
+
+    let reporter = ProgressReporter::new(app, job_id);
+    let report = run_blocking(move || {
+        benchmark::benchmark_models(models, sizes, &|progress| reporter.report(progress))
+    })
+    .await??;
+
+    if let (true, Some(threads), Some(jobs)) = (
+        apply.unwrap_or(false),
+        report.recommended_threads,
+        report.recommended_concurrent_jobs,
+    ) {
+        apply_session_config(SessionConfig {
+            intra_threads: threads,
+            ..session::session_config()
+        })?;
+        pool().set_limit(jobs)?;
+    }
+
+    Ok(report)
+}
+
+/// Saves `config` and unloads every model, so each is loaded again with it on next use.
+fn apply_session_config(config: SessionConfig) -> Result<(), CommandError> {
+    session::set_session_config(config)?;
+    for model in managed_models() {
+        model.unload();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn session_config() -> Result<SessionConfig, CommandError> {
+    Ok(session::session_config())
+}
+
+#[tauri::command]
+pub async fn set_session_config(config: SessionConfig) -> Result<SessionConfig, CommandError> {
+    info!("set_session_config was called with config: {:?}", config);
+
+    apply_session_config(config)?;
+    Ok(config)
+}
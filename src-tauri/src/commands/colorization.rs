@@ -0,0 +1,58 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::run_job;
+use crate::error::CommandError;
+use crate::image::{
+    manager::ModelSlot, model::ColorizationModel, processor::ProgressFn, types::ColorizationParams,
+};
+use crate::output::{save_output, OutputFormat};
+
+pub(crate) static COLORIZATION_MODEL: ModelSlot<ColorizationModel> =
+    ModelSlot::new("colorization", "colorization.onnx");
+
+#[tauri::command]
+pub async fn init_colorization() -> Result<(), CommandError> {
+    COLORIZATION_MODEL.get()?;
+    Ok(())
+}
+
+/// Colorizes `input_path` and writes the result into `output_dir`, returning the output path.
+pub(crate) fn colorize(
+    input_path: &str,
+    output_dir: &str,
+    saturation: Option<f32>,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = COLORIZATION_MODEL.get()?;
+    let params = ColorizationParams {
+        saturation: saturation.unwrap_or(1.0).max(0.0),
+        ..Default::default()
+    };
+
+    let image = processor.process_single_with_progress(input_path, &params, progress)?;
+
+    save_output(&image, input_path, output_dir, "colorized")
+}
+
+#[tauri::command]
+pub async fn colorize_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    saturation: Option<f32>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("colorize_image was called with path: {}", input_path);
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        move |input, output, progress| colorize(input, output, saturation, progress),
+    )
+    .await
+}
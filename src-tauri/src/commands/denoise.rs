@@ -0,0 +1,93 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::{run_job, run_reported};
+use crate::error::CommandError;
+use crate::image::{
+    manager::ModelSlot, model::DenoiseModel, processor::ProgressFn, types::DenoiseParams,
+};
+use crate::output::{save_output, OutputFormat};
+
+pub(crate) static DENOISE_MODEL: ModelSlot<DenoiseModel> =
+    ModelSlot::new("denoise", "denoise.onnx");
+
+#[tauri::command]
+pub async fn init_denoise() -> Result<(), CommandError> {
+    DENOISE_MODEL.get()?;
+    Ok(())
+}
+
+fn denoise_params(strength: Option<f32>) -> DenoiseParams {
+    DenoiseParams {
+        strength: strength.unwrap_or(1.0).clamp(0.0, 1.0),
+        ..Default::default()
+    }
+}
+
+/// Denoises `input_path` with the given strength and writes the result into `output_dir`,
+/// returning the output path.
+pub(crate) fn denoise(
+    input_path: &str,
+    output_dir: &str,
+    strength: Option<f32>,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = DENOISE_MODEL.get()?;
+    let params = denoise_params(strength);
+
+    let image = processor.process_single_with_progress(input_path, &params, progress)?;
+
+    save_output(&image, input_path, output_dir, "denoised")
+}
+
+#[tauri::command]
+pub async fn denoise_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    strength: Option<f32>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("denoise_image was called with path: {}", input_path);
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        move |input, output, progress| denoise(input, output, strength, progress),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn denoise_images(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    output_dir: String,
+    strength: Option<f32>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<(), CommandError> {
+    info!(
+        "denoise_images was called with {} images",
+        input_paths.len()
+    );
+
+    run_reported(app, job_id, output_format, move |progress| {
+        let processor = DENOISE_MODEL.get()?;
+        let params = denoise_params(strength);
+
+        let paths_clone = input_paths.clone();
+        let images = processor.process_batch_with_progress(input_paths, &params, progress)?;
+
+        for (i, image) in images.iter().enumerate() {
+            save_output(image, &paths_clone[i], &output_dir, "denoised")?;
+        }
+
+        Ok(())
+    })
+    .await
+}
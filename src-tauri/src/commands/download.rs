@@ -9,7 +9,10 @@ use tauri::{AppHandle, Emitter};
 use tokio::task;
 use tracing::info;
 
+use crate::error::CommandError;
+use crate::models::managed_models;
 use crate::utils::models_dir;
+use crate::worker::run_blocking;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelInfo {
@@ -26,13 +29,13 @@ struct DownloadProgress {
 }
 
 #[tauri::command]
-pub async fn check_model_exists(model_name: String) -> Result<bool, String> {
+pub async fn check_model_exists(model_name: String) -> Result<bool, CommandError> {
     let models_dir = models_dir();
     let model_path = models_dir.join(&model_name);
 
     if !models_dir.exists() {
         info!("Creating models directory");
-        std::fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&models_dir)?;
     }
 
     info!("Checking if model exists: {}", model_name);
@@ -40,13 +43,14 @@ pub async fn check_model_exists(model_name: String) -> Result<bool, String> {
 }
 
 #[tauri::command]
-pub async fn download_models(app: AppHandle, models: Vec<ModelInfo>) -> Result<(), String> {
+pub async fn download_models(app: AppHandle, models: Vec<ModelInfo>) -> Result<(), CommandError> {
     let client = Client::new();
 
     let models_dir = models_dir();
-    std::fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&models_dir)?;
 
     let total_models = models.len();
+    let file_names: Vec<String> = models.iter().map(|model| model.name.clone()).collect();
 
     // Create download tasks for each model
     let download_tasks: Vec<_> = models
@@ -58,6 +62,7 @@ pub async fn download_models(app: AppHandle, models: Vec<ModelInfo>) -> Result<(
             let app_handle = app.clone();
 
             task::spawn(async move {
+                let model = model_info.name.clone();
                 download_single_model(
                     &client,
                     model_info,
@@ -67,6 +72,7 @@ pub async fn download_models(app: AppHandle, models: Vec<ModelInfo>) -> Result<(
                     index,
                 )
                 .await
+                .map_err(|message| CommandError::Download { model, message })
             })
         })
         .collect();
@@ -76,9 +82,17 @@ pub async fn download_models(app: AppHandle, models: Vec<ModelInfo>) -> Result<(
         .await
         .into_iter()
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?
+        .map_err(|e| CommandError::Internal(e.to_string()))?
         .into_iter()
-        .collect::<Result<Vec<_>, String>>()?;
+        .collect::<Result<Vec<_>, CommandError>>()?;
+
+    // Swap in updated model files for models that are already loaded
+    for model in managed_models() {
+        let status = model.status();
+        if status.loaded && file_names.iter().any(|name| name == status.file_name) {
+            run_blocking(move || model.reload()).await??;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,83 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::run_job;
+use crate::error::CommandError;
+use crate::image::{
+    decode::open_image,
+    enhance::auto_enhance_image,
+    manager::ModelSlot,
+    model::EnhanceModel,
+    processor::{ProcessingStage, Progress, ProgressFn},
+    types::EnhanceParams,
+    validate::validate,
+};
+use crate::output::{save_output, OutputFormat};
+
+pub(crate) static ENHANCE_MODEL: ModelSlot<EnhanceModel> =
+    ModelSlot::new("enhance", "low_light_enhancement.onnx");
+
+#[tauri::command]
+pub async fn init_enhance() -> Result<(), CommandError> {
+    ENHANCE_MODEL.get()?;
+    Ok(())
+}
+
+/// Brightens and corrects the exposure of `input_path` and writes the result into `output_dir`,
+/// returning the output path.
+///
+/// Uses the enhancement model once it has been downloaded and falls back to classic auto levels,
+/// CLAHE and white balance otherwise.
+pub(crate) fn enhance(
+    input_path: &str,
+    output_dir: &str,
+    intensity: Option<f32>,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let intensity = intensity.unwrap_or(1.0).max(0.0);
+
+    let image = if ENHANCE_MODEL.is_available() {
+        let processor = ENHANCE_MODEL.get()?;
+        let params = EnhanceParams {
+            intensity,
+            ..Default::default()
+        };
+
+        processor.process_single_with_progress(input_path, &params, progress)?
+    } else {
+        info!("Enhancement model not available, using classic exposure correction");
+        let report = |stage, fraction| progress(Progress { stage, fraction });
+
+        validate(input_path, 1)?;
+        report(ProcessingStage::Preprocess, 0.0);
+        let image = open_image(input_path)?;
+        report(ProcessingStage::Inference, 0.1);
+        let enhanced = auto_enhance_image(&image, intensity);
+        report(ProcessingStage::Postprocess, 1.0);
+        enhanced
+    };
+
+    save_output(&image, input_path, output_dir, "enhanced")
+}
+
+#[tauri::command]
+pub async fn enhance_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    intensity: Option<f32>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("enhance_image was called with path: {}", input_path);
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        move |input, output, progress| enhance(input, output, intensity, progress),
+    )
+    .await
+}
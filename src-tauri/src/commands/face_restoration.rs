@@ -1,36 +1,32 @@
-use std::path::Path;
-use std::sync::OnceLock;
+use tauri::AppHandle;
 use tracing::info;
 
+use super::run_job;
+use crate::error::CommandError;
 use crate::{
-    image::{model::FaceRestorationModel, processor::ModelProcessor, types::FaceRestorationParams},
-    utils::models_dir,
+    image::{
+        manager::ModelSlot, model::FaceRestorationModel, processor::ProgressFn,
+        types::FaceRestorationParams,
+    },
+    output::{save_output, OutputFormat},
 };
 
-static FACE_RESTORATION_PROCESSOR: OnceLock<ModelProcessor<FaceRestorationModel>> = OnceLock::new();
+pub(crate) static FACE_RESTORATION_MODEL: ModelSlot<FaceRestorationModel> =
+    ModelSlot::new("face_restoration", "face_restoration.onnx");
 
 #[tauri::command]
-pub async fn init_face_restoration() -> Result<(), String> {
-    let models_dir = models_dir();
-    FACE_RESTORATION_PROCESSOR.get_or_init(|| {
-        let model_path = models_dir.join("face_restoration.onnx");
-
-        ModelProcessor::<FaceRestorationModel>::new(model_path.to_str().unwrap())
-            .map_err(|e| e.to_string())
-            .unwrap()
-    });
+pub async fn init_face_restoration() -> Result<(), CommandError> {
+    FACE_RESTORATION_MODEL.get()?;
     Ok(())
 }
 
-fn get_face_restoration_processor() -> &'static ModelProcessor<FaceRestorationModel> {
-    FACE_RESTORATION_PROCESSOR.get().unwrap()
-}
-
-#[tauri::command]
-pub async fn face_restoration(input_path: &str, output_dir: &str) -> Result<String, String> {
-    info!("face_restoration was called with path: {}", input_path);
-
-    let processor = get_face_restoration_processor();
+/// Restores faces in `input_path` and writes the result into `output_dir`, returning the output path.
+pub(crate) fn restore_faces(
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = FACE_RESTORATION_MODEL.get()?;
     let params = FaceRestorationParams {
         model_width: 512,
         model_height: 512,
@@ -40,24 +36,29 @@ pub async fn face_restoration(input_path: &str, output_dir: &str) -> Result<Stri
     };
 
     // Process image through the model
-    let restored = processor
-        .process_single(input_path, &params)
-        .map_err(|e| e.to_string())?;
+    let restored = processor.process_single_with_progress(input_path, &params, progress)?;
 
     // Save the result
-    let name = Path::new(input_path)
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .into_owned();
-    let name = name
-        .strip_suffix(".png")
-        .or_else(|| name.strip_suffix(".jpg"))
-        .or_else(|| name.strip_suffix(".jpeg"))
-        .unwrap_or(&name);
-    let output_path = Path::new(output_dir).join(format!("{}_restored.png", name));
-
-    restored.save(&output_path).map_err(|e| e.to_string())?;
-
-    Ok(output_path.to_str().unwrap().to_string())
+    save_output(&restored, input_path, output_dir, "restored")
+}
+
+#[tauri::command]
+pub async fn face_restoration(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("face_restoration was called with path: {}", input_path);
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        restore_faces,
+    )
+    .await
 }
@@ -1,5 +1,62 @@
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::decode::{image_dimensions, open_image};
+use crate::image::metrics::{self, ImageMetrics};
+use crate::image::validate::{self, InputInfo, InputLimits};
+use crate::image::ImageProcessingError;
+use crate::operation::Operation;
+use crate::worker::run_blocking;
+
 #[tauri::command]
-pub async fn check_image_dimensions(input_path: &str) -> Result<(u32, u32), String> {
-    let dimensions = image::image_dimensions(input_path).unwrap();
-    Ok(dimensions)
+pub async fn check_image_dimensions(input_path: &str) -> Result<(u32, u32), CommandError> {
+    Ok(image_dimensions(input_path)?)
+}
+
+/// Checks `input_path` against the input limits before processing it with `operation`.
+#[tauri::command]
+pub async fn validate_image(
+    input_path: String,
+    operation: Option<Operation>,
+) -> Result<InputInfo, CommandError> {
+    info!("validate_image was called with path: {}", input_path);
+
+    let output_scale = operation.map_or(1, Operation::output_scale);
+    Ok(validate::validate(&input_path, output_scale)?)
+}
+
+#[tauri::command]
+pub async fn input_limits() -> Result<InputLimits, CommandError> {
+    Ok(validate::input_limits())
+}
+
+#[tauri::command]
+pub async fn set_input_limits(limits: InputLimits) -> Result<InputLimits, CommandError> {
+    info!("set_input_limits was called with limits: {:?}", limits);
+
+    validate::set_input_limits(limits)?;
+    Ok(limits)
+}
+
+fn compare(
+    reference_path: &str,
+    candidate_path: &str,
+) -> Result<ImageMetrics, ImageProcessingError> {
+    metrics::compare_resized(&open_image(reference_path)?, &open_image(candidate_path)?)
+}
+
+/// Computes quality metrics of `candidate_path` against `reference_path`, e.g. a result against
+/// its input. A reference of another size is resized to the candidate first, so upscaled results
+/// can be compared too.
+#[tauri::command]
+pub async fn compare_images(
+    reference_path: String,
+    candidate_path: String,
+) -> Result<ImageMetrics, CommandError> {
+    info!(
+        "compare_images was called with paths: {} and {}",
+        reference_path, candidate_path
+    );
+
+    Ok(run_blocking(move || compare(&reference_path, &candidate_path)).await??)
 }
@@ -0,0 +1,73 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::run_job;
+use crate::error::CommandError;
+use crate::image::{
+    manager::ModelSlot,
+    model::InpaintingModel,
+    processor::ProgressFn,
+    types::{InpaintingParams, MaskSource},
+};
+use crate::output::{save_output, OutputFormat};
+
+pub(crate) static INPAINTING_MODEL: ModelSlot<InpaintingModel> =
+    ModelSlot::new("inpainting", "inpainting.onnx");
+
+#[tauri::command]
+pub async fn init_inpainting() -> Result<(), CommandError> {
+    INPAINTING_MODEL.get()?;
+    Ok(())
+}
+
+/// Erases the area of `input_path` marked by `mask` and writes the result into `output_dir`,
+/// returning the output path.
+pub(crate) fn inpaint(
+    input_path: &str,
+    output_dir: &str,
+    mask: MaskSource,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = INPAINTING_MODEL.get()?;
+    let params = InpaintingParams {
+        mask: Some(mask),
+        ..Default::default()
+    };
+
+    let image = processor.process_single_with_progress(input_path, &params, progress)?;
+
+    save_output(&image, input_path, output_dir, "inpainted")
+}
+
+#[tauri::command]
+pub async fn inpaint_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    mask_path: Option<String>,
+    mask_bytes: Option<Vec<u8>>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!("inpaint_image was called with path: {}", input_path);
+
+    let mask = match (mask_path, mask_bytes) {
+        (Some(path), _) => MaskSource::Path(path),
+        (None, Some(bytes)) => MaskSource::Bytes(bytes),
+        (None, None) => {
+            return Err(CommandError::InvalidArgument(
+                "Either mask_path or mask_bytes is required".to_string(),
+            ))
+        }
+    };
+
+    run_job(
+        app,
+        job_id,
+        output_format,
+        input_path,
+        output_dir,
+        move |input, output, progress| inpaint(input, output, mask.clone(), progress),
+    )
+    .await
+}
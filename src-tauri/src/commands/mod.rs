@@ -1,5 +1,69 @@
+pub mod artifact_removal;
 pub mod background_removal;
+pub mod benchmark;
+pub mod colorization;
+pub mod denoise;
 pub mod download;
+pub mod enhance;
 pub mod face_restoration;
 pub mod image;
+pub mod inpainting;
+pub mod models;
+pub mod output;
+pub mod progress;
+pub mod prompt_segmentation;
+pub mod segmentation;
+pub mod server;
 pub mod upscaling;
+pub mod video;
+pub mod watch;
+pub mod worker;
+
+use tauri::AppHandle;
+
+use crate::animation;
+use crate::error::CommandError;
+use crate::image::processor::ProgressFn;
+use crate::output::{with_output_format, OutputFormat};
+use crate::worker::run_blocking;
+use progress::ProgressReporter;
+
+/// Runs `job` on the worker pool with its progress reported to the frontend under `job_id` and
+/// its outputs written in `output_format`.
+pub(crate) async fn run_reported<T, F>(
+    app: AppHandle,
+    job_id: Option<String>,
+    output_format: Option<OutputFormat>,
+    job: F,
+) -> Result<T, CommandError>
+where
+    F: FnOnce(&ProgressFn) -> Result<T, CommandError> + Send + 'static,
+    T: Send + 'static,
+{
+    let reporter = ProgressReporter::new(app, job_id);
+    run_blocking(move || {
+        with_output_format(output_format, || job(&|progress| reporter.report(progress)))
+    })
+    .await?
+}
+
+/// Runs `operation` on `input_path` like [`run_reported`], frame by frame if it is animated.
+///
+/// `operation` is the command's single-image function: it receives a still image path and the
+/// output directory and returns the path it wrote.
+pub(crate) async fn run_job<F>(
+    app: AppHandle,
+    job_id: Option<String>,
+    output_format: Option<OutputFormat>,
+    input_path: String,
+    output_dir: String,
+    operation: F,
+) -> Result<String, CommandError>
+where
+    F: Fn(&str, &str, &ProgressFn) -> Result<String, CommandError> + Send + 'static,
+{
+    run_reported(app, job_id, output_format, move |progress| {
+        animation::run(&input_path, &output_dir, progress, operation)
+    })
+    .await
+}
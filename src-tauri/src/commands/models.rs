@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::time::Duration;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::manager::ModelStatus;
+use crate::models::{find_model, idle_timeout, managed_models, set_idle_timeout};
+use crate::worker::run_blocking;
+
+#[derive(Debug, Serialize)]
+pub struct ModelsStatus {
+    models: Vec<ModelStatus>,
+    idle_timeout_secs: Option<u64>,
+}
+
+#[tauri::command]
+pub async fn model_status() -> Result<ModelsStatus, CommandError> {
+    Ok(ModelsStatus {
+        models: managed_models()
+            .into_iter()
+            .map(|model| model.status())
+            .collect(),
+        idle_timeout_secs: idle_timeout().map(|timeout| timeout.as_secs()),
+    })
+}
+
+#[tauri::command]
+pub async fn load_model(name: String) -> Result<ModelStatus, CommandError> {
+    info!("load_model was called with name: {}", name);
+
+    let model = find_model(&name)?;
+    run_blocking(move || model.load()).await??;
+    Ok(model.status())
+}
+
+#[tauri::command]
+pub async fn reload_model(name: String) -> Result<ModelStatus, CommandError> {
+    info!("reload_model was called with name: {}", name);
+
+    let model = find_model(&name)?;
+    run_blocking(move || model.reload()).await??;
+    Ok(model.status())
+}
+
+#[tauri::command]
+pub async fn unload_model(name: String) -> Result<ModelStatus, CommandError> {
+    info!("unload_model was called with name: {}", name);
+
+    let model = find_model(&name)?;
+    model.unload();
+    Ok(model.status())
+}
+
+#[tauri::command]
+pub async fn set_model_idle_timeout(seconds: Option<u64>) -> Result<(), CommandError> {
+    info!(
+        "set_model_idle_timeout was called with seconds: {:?}",
+        seconds
+    );
+
+    set_idle_timeout(seconds.map(Duration::from_secs))
+}
@@ -0,0 +1,33 @@
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::output::{self, OutputFormat};
+
+#[tauri::command]
+pub async fn output_format() -> Result<OutputFormat, CommandError> {
+    Ok(output::output_format())
+}
+
+#[tauri::command]
+pub async fn set_output_format(format: OutputFormat) -> Result<OutputFormat, CommandError> {
+    info!("set_output_format was called with format: {:?}", format);
+
+    output::set_output_format(format)?;
+    Ok(format)
+}
+
+#[tauri::command]
+pub async fn preserve_color_profile() -> Result<bool, CommandError> {
+    Ok(output::preserve_color_profile())
+}
+
+#[tauri::command]
+pub async fn set_preserve_color_profile(enabled: bool) -> Result<bool, CommandError> {
+    info!(
+        "set_preserve_color_profile was called with enabled: {}",
+        enabled
+    );
+
+    output::set_preserve_color_profile(enabled)?;
+    Ok(enabled)
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+use crate::image::processor::{ProcessingStage, Progress};
+
+#[derive(Debug, Serialize, Clone)]
+struct ProcessingProgress {
+    job_id: String,
+    stage: ProcessingStage,
+    fraction: f32,
+    eta_seconds: Option<f64>,
+}
+
+/// Emits `processing-progress` events for a single job, estimating the remaining time from the
+/// elapsed time and the fraction completed so far.
+pub(crate) struct ProgressReporter {
+    app: AppHandle,
+    job_id: String,
+    started: Instant,
+}
+
+impl ProgressReporter {
+    /// Creates a reporter for `job_id`, generating an ID when the caller did not provide one.
+    pub fn new(app: AppHandle, job_id: Option<String>) -> Self {
+        Self {
+            app,
+            job_id: job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn report(&self, progress: Progress) {
+        let eta_seconds = (progress.fraction > 0.0).then(|| {
+            let elapsed = self.started.elapsed().as_secs_f64();
+            let fraction = progress.fraction as f64;
+            elapsed * (1.0 - fraction) / fraction
+        });
+
+        let payload = ProcessingProgress {
+            job_id: self.job_id.clone(),
+            stage: progress.stage,
+            fraction: progress.fraction,
+            eta_seconds,
+        };
+        let _ = self.app.emit("processing-progress", payload);
+    }
+}
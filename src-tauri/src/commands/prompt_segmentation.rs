@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::{
+    decode::image_dimensions,
+    manager::ModelSlot,
+    model::{PromptDecoderModel, PromptEncoderModel},
+    types::{PromptBox, PromptDecoderParams, PromptEncoderParams, PromptPoint, TensorOutput},
+};
+use crate::utils::output_path;
+use crate::worker::run_blocking;
+
+pub(crate) static PROMPT_ENCODER_MODEL: ModelSlot<PromptEncoderModel> =
+    ModelSlot::new("prompt_encoder", "sam_encoder.onnx");
+pub(crate) static PROMPT_DECODER_MODEL: ModelSlot<PromptDecoderModel> =
+    ModelSlot::new("prompt_decoder", "sam_decoder.onnx");
+
+/// Number of image embeddings kept around; each one is a few megabytes.
+const EMBEDDING_CACHE_SIZE: usize = 4;
+
+static EMBEDDINGS: Mutex<VecDeque<CachedEmbedding>> = Mutex::new(VecDeque::new());
+
+/// Identifies a version of an image file, so edits invalidate its cached embedding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImageKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+#[derive(Clone)]
+struct CachedEmbedding {
+    key: ImageKey,
+    embedding: Arc<TensorOutput<f32>>,
+    width: u32,
+    height: u32,
+}
+
+#[tauri::command]
+pub async fn init_prompt_segmentation() -> Result<(), CommandError> {
+    PROMPT_ENCODER_MODEL.get()?;
+    PROMPT_DECODER_MODEL.get()?;
+    Ok(())
+}
+
+fn image_key(input_path: &str) -> Result<ImageKey, CommandError> {
+    let path = std::fs::canonicalize(input_path)?;
+    let metadata = std::fs::metadata(&path)?;
+    Ok(ImageKey {
+        path,
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+    })
+}
+
+/// Returns the embedding of `input_path`, running the encoder only if it isn't cached yet.
+fn embedding(input_path: &str) -> Result<CachedEmbedding, CommandError> {
+    let key = image_key(input_path)?;
+
+    {
+        let mut cache = EMBEDDINGS.lock().unwrap();
+        if let Some(index) = cache.iter().position(|cached| cached.key == key) {
+            // Move to the back so the least recently used entry is evicted first
+            let cached = cache.remove(index).unwrap();
+            cache.push_back(cached.clone());
+            return Ok(cached);
+        }
+    }
+
+    info!("Encoding {} for prompt segmentation", input_path);
+    let processor = PROMPT_ENCODER_MODEL.get()?;
+    let (width, height) = image_dimensions(input_path)?;
+    let embedding = processor.infer(input_path, &PromptEncoderParams::default())?;
+
+    let cached = CachedEmbedding {
+        key,
+        embedding: Arc::new(embedding),
+        width,
+        height,
+    };
+    let mut cache = EMBEDDINGS.lock().unwrap();
+    cache.push_back(cached.clone());
+    while cache.len() > EMBEDDING_CACHE_SIZE {
+        cache.pop_front();
+    }
+
+    Ok(cached)
+}
+
+/// Segments the object selected by `points` and/or `prompt_box` in `input_path` and writes the
+/// mask into `output_dir`, returning the mask path. Repeated calls on the same image reuse its
+/// embedding, so only the (fast) decoder runs.
+pub(crate) fn segment_with_prompt(
+    input_path: &str,
+    output_dir: &str,
+    points: Vec<PromptPoint>,
+    prompt_box: Option<PromptBox>,
+) -> Result<String, CommandError> {
+    let cached = embedding(input_path)?;
+    let processor = PROMPT_DECODER_MODEL.get()?;
+    let params = PromptDecoderParams {
+        embedding: Some(cached.embedding),
+        points,
+        prompt_box,
+        scale: PromptEncoderParams::default().input_size as f32
+            / cached.width.max(cached.height) as f32,
+        original_width: cached.width,
+        original_height: cached.height,
+    };
+
+    // The embedding stands in for the image, so it isn't validated or read again
+    let input = PromptDecoderModel::input(&params)?;
+    let mask = processor.process_input(input, &params)?;
+
+    let output_path = output_path(input_path, output_dir, "prompt_mask");
+    mask.save(&output_path)?;
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+/// Encodes `input_path` ahead of time, e.g. when the user opens it for selection, so the first
+/// click is as fast as the following ones.
+#[tauri::command]
+pub async fn prepare_prompt_segmentation(input_path: String) -> Result<(), CommandError> {
+    info!(
+        "prepare_prompt_segmentation was called with path: {}",
+        input_path
+    );
+
+    run_blocking(move || embedding(&input_path).map(|_| ())).await?
+}
+
+#[tauri::command]
+pub async fn prompt_segmentation(
+    input_path: String,
+    output_dir: String,
+    points: Vec<PromptPoint>,
+    prompt_box: Option<PromptBox>,
+) -> Result<String, CommandError> {
+    info!(
+        "prompt_segmentation was called with path: {} ({} points, box: {})",
+        input_path,
+        points.len(),
+        prompt_box.is_some()
+    );
+
+    run_blocking(move || segment_with_prompt(&input_path, &output_dir, points, prompt_box)).await?
+}
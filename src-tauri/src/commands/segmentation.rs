@@ -0,0 +1,249 @@
+use image::{DynamicImage, GrayImage, Luma};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tracing::info;
+
+use super::run_reported;
+use crate::error::CommandError;
+use crate::image::{
+    decode::open_image, manager::ModelSlot, mask::apply_mask, model::SegmentationModel,
+    processor::ProgressFn, types::SegmentationParams, validate::validate,
+};
+use crate::output::{save_output, OutputFormat};
+use crate::utils::output_path;
+
+pub(crate) static SEGMENTATION_MODEL: ModelSlot<SegmentationModel> =
+    ModelSlot::new("segmentation", "segmentation.onnx");
+
+/// Class names of the segmentation model's outputs, in order (Cityscapes).
+pub const SEGMENTATION_CLASSES: [&str; 19] = [
+    "road",
+    "sidewalk",
+    "building",
+    "wall",
+    "fence",
+    "pole",
+    "traffic_light",
+    "traffic_sign",
+    "vegetation",
+    "terrain",
+    "sky",
+    "person",
+    "rider",
+    "car",
+    "truck",
+    "bus",
+    "train",
+    "motorcycle",
+    "bicycle",
+];
+
+/// Names that select several classes at once.
+const CLASS_GROUPS: [(&str, &[&str]); 1] = [(
+    "vehicle",
+    &["car", "truck", "bus", "train", "motorcycle", "bicycle"],
+)];
+
+/// What happens to the pixels of the selected classes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentationMode {
+    /// Keep the selected classes and make everything else transparent.
+    Keep,
+    /// Make the selected classes transparent (or fill them with a replacement image).
+    Remove,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassMask {
+    pub class: &'static str,
+    pub path: String,
+    /// Fraction of the image covered by the class.
+    pub coverage: f32,
+}
+
+#[tauri::command]
+pub async fn init_segmentation() -> Result<(), CommandError> {
+    SEGMENTATION_MODEL.get()?;
+    Ok(())
+}
+
+/// Runs the segmentation model on `input_path`, returning the label map and the decoded input.
+fn segment(
+    input_path: &str,
+    progress: &ProgressFn,
+) -> Result<(GrayImage, DynamicImage), CommandError> {
+    let processor = SEGMENTATION_MODEL.get()?;
+    let params = SegmentationParams::default();
+
+    let (labels, params) = processor.process_single_with_params(input_path, &params, progress)?;
+    let original = params.original.ok_or_else(|| {
+        CommandError::Processing("Segmentation did not keep the input".to_string())
+    })?;
+
+    Ok((labels.into_luma8(), original))
+}
+
+/// Resolves class and group names to class indices.
+fn class_indices(classes: &[String]) -> Result<Vec<u8>, CommandError> {
+    let mut indices = Vec::new();
+    for class in classes {
+        let names = CLASS_GROUPS
+            .iter()
+            .find(|(group, _)| group == class)
+            .map_or_else(|| vec![class.as_str()], |(_, members)| members.to_vec());
+
+        for name in names {
+            let index = SEGMENTATION_CLASSES
+                .iter()
+                .position(|&known| known == name)
+                .ok_or_else(|| {
+                    CommandError::InvalidArgument(format!("Unknown segmentation class: {}", name))
+                })?;
+            indices.push(index as u8);
+        }
+    }
+
+    Ok(indices)
+}
+
+/// Builds a mask of the pixels labelled with any of `classes`, upscaled to `width`x`height` with
+/// soft edges.
+fn class_mask(labels: &GrayImage, classes: &[u8], width: u32, height: u32) -> GrayImage {
+    let mask = GrayImage::from_fn(labels.width(), labels.height(), |x, y| {
+        let selected = classes.contains(&labels.get_pixel(x, y)[0]);
+        Luma([if selected { 255 } else { 0 }])
+    });
+
+    image::imageops::resize(&mask, width, height, image::imageops::FilterType::Triangle)
+}
+
+/// Writes one mask per class present in `input_path` into `output_dir`.
+pub(crate) fn class_masks(
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<Vec<ClassMask>, CommandError> {
+    let (labels, original) = segment(input_path, progress)?;
+    let (width, height) = (original.width(), original.height());
+    drop(original);
+
+    let mut counts = [0usize; 256];
+    for pixel in labels.pixels() {
+        counts[pixel[0] as usize] += 1;
+    }
+    let total = (labels.width() * labels.height()).max(1) as f32;
+
+    let mut masks = Vec::new();
+    for (index, class) in SEGMENTATION_CLASSES.iter().enumerate() {
+        if counts[index] == 0 {
+            continue;
+        }
+
+        let mask = class_mask(&labels, &[index as u8], width, height);
+        let output_path = output_path(input_path, output_dir, &format!("mask_{}", class));
+        mask.save(&output_path)?;
+
+        masks.push(ClassMask {
+            class,
+            path: output_path.to_str().unwrap().to_string(),
+            coverage: counts[index] as f32 / total,
+        });
+    }
+
+    Ok(masks)
+}
+
+/// Keeps or removes the given classes of `input_path` and writes the result into `output_dir`,
+/// returning the output path. Removed areas are transparent, or filled with `replacement_path`
+/// (stretched to the image size) when given, e.g. to replace the sky.
+pub(crate) fn segment_classes(
+    input_path: &str,
+    output_dir: &str,
+    classes: &[String],
+    mode: SegmentationMode,
+    replacement_path: Option<&str>,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let indices = class_indices(classes)?;
+    if let Some(replacement_path) = replacement_path {
+        validate(replacement_path, 1)?;
+    }
+    let (labels, original) = segment(input_path, progress)?;
+
+    let mut mask = class_mask(&labels, &indices, original.width(), original.height());
+    if mode == SegmentationMode::Remove {
+        image::imageops::invert(&mut mask);
+    }
+    let foreground = apply_mask(&original, &mask);
+
+    let result = match replacement_path {
+        Some(replacement_path) => {
+            let background = open_image(replacement_path)?.resize_exact(
+                original.width(),
+                original.height(),
+                image::imageops::FilterType::Lanczos3,
+            );
+            match &foreground {
+                DynamicImage::ImageRgba8(foreground) => {
+                    let mut background = background.into_rgba8();
+                    image::imageops::overlay(&mut background, foreground, 0, 0);
+                    DynamicImage::ImageRgba8(background)
+                }
+                foreground => {
+                    let mut background = background.into_rgba32f();
+                    image::imageops::overlay(&mut background, &foreground.to_rgba32f(), 0, 0);
+                    DynamicImage::ImageRgba32F(background)
+                }
+            }
+        }
+        None => foreground,
+    };
+
+    save_output(&result, input_path, output_dir, "segmented")
+}
+
+#[tauri::command]
+pub async fn segmentation_masks(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    job_id: Option<String>,
+) -> Result<Vec<ClassMask>, CommandError> {
+    info!("segmentation_masks was called with path: {}", input_path);
+
+    run_reported(app, job_id, None, move |progress| {
+        class_masks(&input_path, &output_dir, progress)
+    })
+    .await
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn segment_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    classes: Vec<String>,
+    mode: SegmentationMode,
+    replacement_path: Option<String>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!(
+        "segment_image was called with path: {} ({:?} {:?})",
+        input_path, mode, classes
+    );
+
+    run_reported(app, job_id, output_format, move |progress| {
+        segment_classes(
+            &input_path,
+            &output_dir,
+            &classes,
+            mode,
+            replacement_path.as_deref(),
+            progress,
+        )
+    })
+    .await
+}
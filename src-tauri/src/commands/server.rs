@@ -0,0 +1,72 @@
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::server::{self, generate_token, load_config, running_port, save_config};
+
+#[derive(Debug, Serialize)]
+pub struct ApiServerStatus {
+    enabled: bool,
+    running: bool,
+    port: u16,
+    token: String,
+}
+
+fn status() -> ApiServerStatus {
+    let config = load_config();
+    ApiServerStatus {
+        enabled: config.enabled,
+        running: running_port().is_some(),
+        port: running_port().unwrap_or(config.port),
+        token: config.token,
+    }
+}
+
+#[tauri::command]
+pub async fn api_server_status() -> Result<ApiServerStatus, CommandError> {
+    Ok(status())
+}
+
+#[tauri::command]
+pub async fn enable_api_server(port: Option<u16>) -> Result<ApiServerStatus, CommandError> {
+    info!("enable_api_server was called with port: {:?}", port);
+
+    let mut config = load_config();
+    config.enabled = true;
+    if let Some(port) = port {
+        config.port = port;
+    }
+
+    server::start(&config).await?;
+    save_config(&config)?;
+
+    Ok(status())
+}
+
+#[tauri::command]
+pub async fn disable_api_server() -> Result<ApiServerStatus, CommandError> {
+    info!("disable_api_server was called");
+
+    let mut config = load_config();
+    config.enabled = false;
+
+    server::stop().await;
+    save_config(&config)?;
+
+    Ok(status())
+}
+
+#[tauri::command]
+pub async fn regenerate_api_token() -> Result<ApiServerStatus, CommandError> {
+    info!("regenerate_api_token was called");
+
+    let mut config = load_config();
+    config.token = generate_token();
+    save_config(&config)?;
+
+    if running_port().is_some() {
+        server::start(&config).await?;
+    }
+
+    Ok(status())
+}
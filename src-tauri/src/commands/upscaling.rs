@@ -1,85 +1,89 @@
-use std::path::Path;
-use std::sync::OnceLock;
+use tauri::AppHandle;
 use tracing::info;
 
-use crate::image::{model::UpscalingModel, processor::ModelProcessor, types::UpscalingParams};
-use crate::utils::models_dir;
+use super::{run_job, run_reported};
+use crate::error::CommandError;
+use crate::image::{
+    manager::ModelSlot, model::UpscalingModel, processor::ProgressFn, types::UpscalingParams,
+};
+use crate::operation::{run_pipeline_with_progress, Operation};
+use crate::output::{save_output, OutputFormat};
 
-static UPSCALE_PROCESSOR: OnceLock<ModelProcessor<UpscalingModel>> = OnceLock::new();
+pub(crate) static UPSCALE_MODEL: ModelSlot<UpscalingModel> =
+    ModelSlot::new("upscaling", "image_upscaling.onnx");
 
 #[tauri::command]
-pub async fn init_upscaling() -> Result<(), String> {
-    let models_dir = models_dir();
-    UPSCALE_PROCESSOR.get_or_init(|| {
-        let model_path = models_dir.join("image_upscaling.onnx");
-
-        ModelProcessor::<UpscalingModel>::new(model_path.to_str().unwrap())
-            .map_err(|e| e.to_string())
-            .unwrap()
-    });
+pub async fn init_upscaling() -> Result<(), CommandError> {
+    UPSCALE_MODEL.get()?;
     Ok(())
 }
 
-fn get_upscale_processor() -> &'static ModelProcessor<UpscalingModel> {
-    UPSCALE_PROCESSOR.get().unwrap()
+/// Upscales `input_path` and writes the result into `output_dir`, returning the output path.
+pub(crate) fn upscale(
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let processor = UPSCALE_MODEL.get()?;
+    let params = UpscalingParams {};
+
+    let image = processor.process_single_with_progress(input_path, &params, progress)?;
+
+    save_output(&image, input_path, output_dir, "upscaled")
 }
 
 #[tauri::command]
-pub async fn upscale_image(input_path: &str, output_dir: &str) -> Result<String, String> {
+pub async fn upscale_image(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    remove_artifacts: Option<bool>,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
     info!("upscale_image was called with path: {}", input_path);
 
-    let processor = get_upscale_processor();
-    let params = UpscalingParams {};
-
-    let image = processor
-        .process_single(input_path, &params)
-        .map_err(|e| e.to_string())?;
-
-    let name = Path::new(input_path)
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .into_owned();
-    let name = name
-        .strip_suffix(".png")
-        .or_else(|| name.strip_suffix(".jpg"))
-        .or_else(|| name.strip_suffix(".jpeg"))
-        .unwrap_or(&name);
-    let output_path = Path::new(output_dir).join(format!("{}_upscaled.png", name));
-    image.save(&output_path).map_err(|e| e.to_string())?;
-
-    Ok(output_path.to_str().unwrap().to_string())
+    // Compression artifacts get amplified by upscaling, so clean them up first if asked.
+    if remove_artifacts.unwrap_or(false) {
+        run_reported(app, job_id, output_format, move |progress| {
+            run_pipeline_with_progress(
+                &[Operation::RemoveArtifacts, Operation::Upscale],
+                &input_path,
+                &output_dir,
+                progress,
+            )
+        })
+        .await
+    } else {
+        run_job(app, job_id, output_format, input_path, output_dir, upscale).await
+    }
 }
 
 #[tauri::command]
-pub async fn upscale_images(input_paths: Vec<String>, output_dir: &str) -> Result<(), String> {
+pub async fn upscale_images(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    output_dir: String,
+    output_format: Option<OutputFormat>,
+    job_id: Option<String>,
+) -> Result<(), CommandError> {
     info!(
         "upscale_images was called with {} images",
         input_paths.len()
     );
 
-    let processor = get_upscale_processor();
-    let params = UpscalingParams {};
+    run_reported(app, job_id, output_format, move |progress| {
+        let processor = UPSCALE_MODEL.get()?;
+        let params = UpscalingParams {};
 
-    let paths_clone = input_paths.clone();
-    let images = processor
-        .process_batch(input_paths, &params)
-        .map_err(|e| e.to_string())?;
+        let paths_clone = input_paths.clone();
+        let images = processor.process_batch_with_progress(input_paths, &params, progress)?;
 
-    for (i, image) in images.iter().enumerate() {
-        let name = Path::new(&paths_clone[i])
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .into_owned();
-        let name = name
-            .strip_suffix(".png")
-            .or_else(|| name.strip_suffix(".jpg"))
-            .or_else(|| name.strip_suffix(".jpeg"))
-            .unwrap_or(&name);
-        let output_path = Path::new(output_dir).join(format!("{}_upscaled.png", name));
-        image.save(&output_path).map_err(|e| e.to_string())?;
-    }
+        for (i, image) in images.iter().enumerate() {
+            save_output(image, &paths_clone[i], &output_dir, "upscaled")?;
+        }
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
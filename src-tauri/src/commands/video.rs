@@ -0,0 +1,42 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::progress::ProgressReporter;
+use crate::error::CommandError;
+use crate::operation::Operation;
+use crate::video::{self, find_ffmpeg};
+use crate::worker::{run_blocking, CancelToken};
+
+#[tauri::command]
+pub async fn ffmpeg_available() -> Result<bool, CommandError> {
+    Ok(find_ffmpeg().is_some())
+}
+
+/// Runs `pipeline` over every frame of a video. Cancelling the job with `cancel_job` keeps the
+/// frames processed so far, and calling this again with the same input and pipeline resumes.
+#[tauri::command]
+pub async fn process_video(
+    app: AppHandle,
+    input_path: String,
+    output_dir: String,
+    pipeline: Vec<Operation>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    info!(
+        "process_video was called with path: {} and pipeline: {:?}",
+        input_path, pipeline
+    );
+
+    let cancel = CancelToken::new(job_id.clone());
+    let reporter = ProgressReporter::new(app, job_id);
+    run_blocking(move || {
+        video::process_video(
+            &input_path,
+            &output_dir,
+            &pipeline,
+            &|progress| reporter.report(progress),
+            &cancel,
+        )
+    })
+    .await?
+}
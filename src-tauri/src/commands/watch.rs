@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::operation::Operation;
+use crate::watch::{manager_ref, read_history, WatchFolder, WatchRecord};
+
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+#[tauri::command]
+pub async fn list_watch_folders() -> Result<Vec<WatchFolder>, CommandError> {
+    Ok(manager_ref()?.folders())
+}
+
+#[tauri::command]
+pub async fn add_watch_folder(
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    pipeline: Vec<Operation>,
+) -> Result<WatchFolder, CommandError> {
+    info!(
+        "add_watch_folder was called with {} -> {}",
+        input_dir.display(),
+        output_dir.display()
+    );
+
+    manager_ref()?.add(input_dir, output_dir, pipeline)
+}
+
+#[tauri::command]
+pub async fn remove_watch_folder(id: String) -> Result<(), CommandError> {
+    info!("remove_watch_folder was called with id: {}", id);
+
+    manager_ref()?.remove(&id)
+}
+
+#[tauri::command]
+pub async fn watch_history(limit: Option<usize>) -> Result<Vec<WatchRecord>, CommandError> {
+    read_history(limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+}
@@ -0,0 +1,25 @@
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::worker::{pool, WorkerStatus};
+
+#[tauri::command]
+pub async fn worker_status() -> Result<WorkerStatus, CommandError> {
+    Ok(pool().status())
+}
+
+/// Asks the job with `job_id` to stop; returns whether a cancellable job with that id is running.
+#[tauri::command]
+pub async fn cancel_job(job_id: String) -> Result<bool, CommandError> {
+    info!("cancel_job was called with id: {}", job_id);
+
+    Ok(crate::worker::cancel_job(&job_id))
+}
+
+#[tauri::command]
+pub async fn set_max_concurrent_jobs(limit: usize) -> Result<WorkerStatus, CommandError> {
+    info!("set_max_concurrent_jobs was called with limit: {}", limit);
+
+    pool().set_limit(limit)?;
+    Ok(pool().status())
+}
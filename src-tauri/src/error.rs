@@ -0,0 +1,161 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::image::validate::ValidationError;
+use crate::image::ImageProcessingError;
+
+/// Error returned by commands, serialized as `{ code, message, details }`. The frontend shows a
+/// localized text by `code`, filled in from `details`, and falls back to `message`.
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error(transparent)]
+    Validation(ValidationError),
+    #[error("Model {model} was not found at {path}")]
+    ModelNotFound { model: String, path: String },
+    #[error("Model {model} could not be loaded: {message}")]
+    ModelLoad { model: String, message: String },
+    #[error("Inference failed: {0}")]
+    Inference(String),
+    #[error("Image error: {0}")]
+    Image(String),
+    #[error("IO error: {0}")]
+    Io(String),
+    #[error("Downloading {model} failed: {message}")]
+    Download { model: String, message: String },
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("ffmpeg was not found; install it or place it next to the application")]
+    FfmpegNotFound,
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Processing(String),
+    /// A failure of the app itself, e.g. a panicking worker job.
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl CommandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::Validation(error) => match error {
+                ValidationError::NotFound { .. } => "file_not_found",
+                ValidationError::UnsupportedFormat { .. } => "unsupported_format",
+                ValidationError::Corrupt { .. } => "corrupt_image",
+                ValidationError::FileTooLarge { .. } => "file_too_large",
+                ValidationError::TooManyPixels { .. } => "too_many_pixels",
+                ValidationError::InsufficientMemory { .. } => "insufficient_memory",
+            },
+            CommandError::ModelNotFound { .. } => "model_not_found",
+            CommandError::ModelLoad { .. } => "model_load_failed",
+            CommandError::Inference(_) => "inference_failed",
+            CommandError::Image(_) => "image_error",
+            CommandError::Io(_) => "io_error",
+            CommandError::Download { .. } => "download_failed",
+            CommandError::Cancelled => "cancelled",
+            CommandError::FfmpegNotFound => "ffmpeg_not_found",
+            CommandError::Ffmpeg(_) => "ffmpeg_failed",
+            CommandError::InvalidArgument(_) => "invalid_argument",
+            CommandError::Processing(_) => "processing_failed",
+            CommandError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Structured context such as the path or model name, for the localized message.
+    pub fn details(&self) -> Value {
+        match self {
+            CommandError::Validation(error) => {
+                let mut details = serde_json::to_value(error).unwrap_or_default();
+                if let Some(details) = details.as_object_mut() {
+                    details.remove("kind");
+                }
+                details
+            }
+            CommandError::ModelNotFound { model, path } => json!({ "model": model, "path": path }),
+            CommandError::ModelLoad { model, .. } | CommandError::Download { model, .. } => {
+                json!({ "model": model })
+            }
+            _ => json!({}),
+        }
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("CommandError", 3)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("details", &self.details())?;
+        error.end()
+    }
+}
+
+impl From<ImageProcessingError> for CommandError {
+    fn from(error: ImageProcessingError) -> Self {
+        match error {
+            ImageProcessingError::Io(e) => CommandError::Io(e.to_string()),
+            ImageProcessingError::Ort(message) => CommandError::Inference(message),
+            ImageProcessingError::Processing(message) => CommandError::Processing(message),
+            ImageProcessingError::Image(e) => CommandError::Image(e.to_string()),
+            ImageProcessingError::Validation(e) => CommandError::Validation(e),
+            ImageProcessingError::ModelNotFound { model, path } => {
+                CommandError::ModelNotFound { model, path }
+            }
+            ImageProcessingError::ModelLoad { model, message } => {
+                CommandError::ModelLoad { model, message }
+            }
+        }
+    }
+}
+
+impl From<ValidationError> for CommandError {
+    fn from(error: ValidationError) -> Self {
+        CommandError::Validation(error)
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        CommandError::Io(error.to_string())
+    }
+}
+
+impl From<image::ImageError> for CommandError {
+    fn from(error: image::ImageError) -> Self {
+        CommandError::Image(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_of(error: CommandError) -> Value {
+        serde_json::to_value(error).unwrap()["code"].clone()
+    }
+
+    #[test]
+    fn errors_serialize_with_their_code() {
+        assert_eq!(
+            code_of(crate::models::find_model("missing").err().unwrap()),
+            "invalid_argument"
+        );
+        assert_eq!(code_of(CommandError::FfmpegNotFound), "ffmpeg_not_found");
+        assert_eq!(
+            code_of(std::io::Error::other("disk full").into()),
+            "io_error"
+        );
+        assert_eq!(
+            code_of(
+                ValidationError::NotFound {
+                    path: "a.png".to_string()
+                }
+                .into()
+            ),
+            "file_not_found"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! sRGB <-> CIE Lab conversions (D65 white point).
+
+use image::DynamicImage;
+
+const WHITE_X: f32 = 0.950_47;
+const WHITE_Y: f32 = 1.0;
+const WHITE_Z: f32 = 1.088_83;
+const EPSILON: f32 = 216.0 / 24389.0;
+const KAPPA: f32 = 24389.0 / 27.0;
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn lab_f(t: f32) -> f32 {
+    if t > EPSILON {
+        t.cbrt()
+    } else {
+        (KAPPA * t + 16.0) / 116.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    let t3 = t * t * t;
+    if t3 > EPSILON {
+        t3
+    } else {
+        (116.0 * t - 16.0) / KAPPA
+    }
+}
+
+/// Converts an sRGB pixel in `0.0..=1.0` to Lab, with L in `0..=100`.
+pub fn rgb_to_lab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+
+    let x = 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = 0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b;
+
+    let (fx, fy, fz) = (lab_f(x / WHITE_X), lab_f(y / WHITE_Y), lab_f(z / WHITE_Z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Converts a Lab pixel back to sRGB in `0.0..=1.0`, clamping out-of-gamut values.
+pub fn lab_to_rgb([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let x = lab_f_inv(fx) * WHITE_X;
+    let y = lab_f_inv(fy) * WHITE_Y;
+    let z = lab_f_inv(fz) * WHITE_Z;
+
+    let r = 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z;
+    let g = -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z;
+    let b = 0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z;
+
+    [r, g, b].map(|v| linear_to_srgb(v.max(0.0)).clamp(0.0, 1.0))
+}
+
+/// Converts a processed `image` back to the layout of the input it came from: RGBA if `like` has
+/// alpha and RGB otherwise, with the 8-bit, 16-bit or float samples of `like`.
+pub fn match_color_type(image: DynamicImage, like: &DynamicImage) -> DynamicImage {
+    let color = like.color();
+    match (
+        color.bytes_per_pixel() / color.channel_count(),
+        color.has_alpha(),
+    ) {
+        (1, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        (1, true) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        (2, false) => DynamicImage::ImageRgb16(image.to_rgb16()),
+        (2, true) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        (_, false) => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+        (_, true) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+    }
+}
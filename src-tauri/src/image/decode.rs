@@ -0,0 +1,290 @@
+//! Opening input images, including camera RAW files the `image` crate can't read.
+
+use image::{DynamicImage, ImageReader};
+use std::io::{BufRead, Cursor, Seek};
+use std::path::Path;
+
+#[cfg(feature = "raw")]
+use crate::image::color::linear_to_srgb;
+use crate::image::error::ImageProcessingError;
+use crate::image::icc::{read_profile, to_srgb};
+use crate::image::validate::{decoder_limits, input_limits, ValidationError};
+
+/// Extensions of the formats decoded through the `image` crate. AVIF is only decoded with the
+/// `avif` feature.
+pub const IMAGE_EXTENSIONS: [&str; 10] = [
+    "png", "jpg", "jpeg", "webp", "bmp", "tif", "tiff", "gif", "exr", "avif",
+];
+
+/// Extensions of the HEIF container formats decoded through libheif.
+pub const HEIF_EXTENSIONS: [&str; 2] = ["heic", "heif"];
+
+/// Extensions of the camera RAW formats decoded through the RAW pipeline.
+pub const RAW_EXTENSIONS: [&str; 12] = [
+    "arw", "cr2", "crw", "dng", "erf", "mrw", "nef", "orf", "pef", "raf", "rw2", "srw",
+];
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub fn is_raw(path: &str) -> bool {
+    has_extension(path, &RAW_EXTENSIONS)
+}
+
+pub fn is_heif(path: &str) -> bool {
+    has_extension(path, &HEIF_EXTENSIONS)
+}
+
+/// Whether `path` has the extension of a format this build can open, e.g. to pick inputs out of
+/// a folder.
+pub fn is_supported(path: &str) -> bool {
+    if is_raw(path) {
+        cfg!(feature = "raw")
+    } else if is_heif(path) {
+        cfg!(feature = "heic")
+    } else if has_extension(path, &["avif"]) {
+        cfg!(feature = "avif")
+    } else {
+        has_extension(path, &IMAGE_EXTENSIONS)
+    }
+}
+
+/// File extension for the format of an encoded image, recognizing HEIC in addition to what the
+/// `image` crate knows.
+pub fn sniff_extension(bytes: &[u8]) -> Option<&'static str> {
+    if let Ok(format) = image::guess_format(bytes) {
+        return format.extensions_str().first().copied();
+    }
+    // ISO base media file with an HEIF brand, e.g. "....ftypheic"
+    let brand = bytes.get(4..12)?;
+    [
+        b"ftypheic",
+        b"ftypheix",
+        b"ftyphevc",
+        b"ftypmif1",
+        b"ftypmsf1",
+    ]
+    .contains(&brand.try_into().ok()?)
+    .then_some("heic")
+}
+
+/// Opens an input image in sRGB, converting from its embedded ICC profile if it has another one.
+/// Camera RAW files are decoded to a white-balanced image with 32-bit float samples.
+pub fn open_image(path: &str) -> Result<DynamicImage, ImageProcessingError> {
+    let image = if is_raw(path) {
+        open_raw(path)?
+    } else if is_heif(path) {
+        open_heif(path)?
+    } else {
+        check_avif_support(path)?;
+        decode_limited(ImageReader::open(path)?.with_guessed_format()?)?
+    };
+
+    match read_profile(path) {
+        Some(profile) => to_srgb(image, &profile),
+        None => Ok(image),
+    }
+}
+
+/// Decodes an image held in memory, such as a mask drawn in the UI, rejecting it like
+/// [`validate`](crate::image::validate::validate) would if it has too many pixels.
+pub fn decode_bytes(bytes: &[u8]) -> Result<DynamicImage, ImageProcessingError> {
+    let reader = || ImageReader::new(Cursor::new(bytes)).with_guessed_format();
+    let (width, height) = reader()?.into_dimensions()?;
+    let max_pixels = input_limits().max_pixels;
+    if width as u64 * height as u64 > max_pixels {
+        return Err(ValidationError::TooManyPixels {
+            width,
+            height,
+            max_pixels,
+        }
+        .into());
+    }
+
+    decode_limited(reader()?)
+}
+
+fn decode_limited<R: BufRead + Seek>(
+    mut reader: ImageReader<R>,
+) -> Result<DynamicImage, ImageProcessingError> {
+    reader.limits(decoder_limits());
+    Ok(reader.decode()?)
+}
+
+/// Returns the size of an input image as [`open_image`] would decode it.
+pub fn image_dimensions(path: &str) -> Result<(u32, u32), ImageProcessingError> {
+    if is_raw(path) {
+        raw_dimensions(path)
+    } else if is_heif(path) {
+        heif_dimensions(path)
+    } else {
+        check_avif_support(path)?;
+        Ok(ImageReader::open(path)?
+            .with_guessed_format()?
+            .into_dimensions()?)
+    }
+}
+
+/// Rejects AVIF inputs when the decoder isn't built in, as an unsupported format rather than the
+/// `image` crate's generic decoding error.
+fn check_avif_support(path: &str) -> Result<(), ImageProcessingError> {
+    if cfg!(feature = "avif") || !has_extension(path, &["avif"]) {
+        return Ok(());
+    }
+    Err(unsupported_format(path))
+}
+
+/// Error for inputs in a format whose decoder was not enabled in this build.
+fn unsupported_format(path: &str) -> ImageProcessingError {
+    ValidationError::UnsupportedFormat {
+        path: path.to_string(),
+    }
+    .into()
+}
+
+/// Demosaics and white-balances a RAW file into linear sRGB primaries, then applies the sRGB
+/// transfer curve the models expect. The curve is applied here in `f32` rather than in
+/// imagepipe's 16-bit output to keep shadow detail.
+#[cfg(feature = "raw")]
+fn open_raw(path: &str) -> Result<DynamicImage, ImageProcessingError> {
+    let mut pipeline = imagepipe::Pipeline::new_from_file(path)
+        .map_err(|e| ImageProcessingError::Processing(format!("Failed to read RAW file: {}", e)))?;
+    pipeline.globals.settings.linear = true;
+
+    let decoded = pipeline.output_16bit(None).map_err(|e| {
+        ImageProcessingError::Processing(format!("Failed to decode RAW file: {}", e))
+    })?;
+
+    let samples = decoded
+        .data
+        .iter()
+        .map(|&v| linear_to_srgb(v as f32 / u16::MAX as f32))
+        .collect();
+    let image = image::Rgb32FImage::from_raw(decoded.width as u32, decoded.height as u32, samples)
+        .ok_or_else(|| ImageProcessingError::Processing("Invalid RAW image size".to_string()))?;
+
+    Ok(DynamicImage::ImageRgb32F(image))
+}
+
+/// Reads the size of a RAW file from its metadata, after the crop and rotation the RAW pipeline
+/// applies, without decoding the sensor data.
+#[cfg(feature = "raw")]
+fn raw_dimensions(path: &str) -> Result<(u32, u32), ImageProcessingError> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let raw = rawloader::decode_dummy(&mut reader)
+        .map_err(|e| ImageProcessingError::Processing(format!("Failed to read RAW file: {}", e)))?;
+
+    let [top, right, bottom, left] = raw.crops;
+    let width = raw.width.saturating_sub(left + right) as u32;
+    let height = raw.height.saturating_sub(top + bottom) as u32;
+    let (transposed, _, _) = raw.orientation.to_flips();
+    Ok(if transposed {
+        (height, width)
+    } else {
+        (width, height)
+    })
+}
+
+#[cfg(not(feature = "raw"))]
+fn open_raw(path: &str) -> Result<DynamicImage, ImageProcessingError> {
+    Err(unsupported_format(path))
+}
+
+#[cfg(not(feature = "raw"))]
+fn raw_dimensions(path: &str) -> Result<(u32, u32), ImageProcessingError> {
+    Err(unsupported_format(path))
+}
+
+#[cfg(feature = "heic")]
+fn heif_error(e: libheif_rs::HeifError) -> ImageProcessingError {
+    ImageProcessingError::Processing(format!("Failed to decode HEIF file: {}", e))
+}
+
+/// Decodes the primary image of a HEIC/HEIF file, keeping more than 8 bits per channel as 16-bit.
+#[cfg(feature = "heic")]
+fn open_heif(path: &str) -> Result<DynamicImage, ImageProcessingError> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_file(path).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    let alpha = handle.has_alpha_channel();
+    let high_bit_depth = handle.luma_bits_per_pixel() > 8;
+    let chroma = match (high_bit_depth, alpha) {
+        (false, false) => RgbChroma::Rgb,
+        (false, true) => RgbChroma::Rgba,
+        (true, false) => RgbChroma::HdrRgbLe,
+        (true, true) => RgbChroma::HdrRgbaLe,
+    };
+
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(heif_error)?;
+    let planes = decoded.planes();
+    let plane = planes.interleaved.ok_or_else(|| {
+        ImageProcessingError::Processing("HEIF decoder returned no image data".to_string())
+    })?;
+
+    let channels = if alpha { 4 } else { 3 };
+    let (width, height) = (plane.width, plane.height);
+    let row_bytes = width as usize * channels * if high_bit_depth { 2 } else { 1 };
+    // Rows may be padded, so copy them out without the stride
+    let rows = plane
+        .data
+        .chunks(plane.stride)
+        .take(height as usize)
+        .flat_map(|row| &row[..row_bytes]);
+    let invalid = || ImageProcessingError::Processing("Invalid HEIF image size".to_string());
+
+    let image = if high_bit_depth {
+        // Samples hold `bits_per_pixel` bits, e.g. 10, scaled up to the full 16-bit range
+        let shift = 16 - plane.bits_per_pixel.min(16);
+        let bytes: Vec<u8> = rows.copied().collect();
+        let samples: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]) << shift)
+            .collect();
+        if alpha {
+            DynamicImage::ImageRgba16(
+                image::ImageBuffer::from_raw(width, height, samples).ok_or_else(invalid)?,
+            )
+        } else {
+            DynamicImage::ImageRgb16(
+                image::ImageBuffer::from_raw(width, height, samples).ok_or_else(invalid)?,
+            )
+        }
+    } else {
+        let samples: Vec<u8> = rows.copied().collect();
+        if alpha {
+            DynamicImage::ImageRgba8(
+                image::ImageBuffer::from_raw(width, height, samples).ok_or_else(invalid)?,
+            )
+        } else {
+            DynamicImage::ImageRgb8(
+                image::ImageBuffer::from_raw(width, height, samples).ok_or_else(invalid)?,
+            )
+        }
+    };
+
+    Ok(image)
+}
+
+#[cfg(feature = "heic")]
+fn heif_dimensions(path: &str) -> Result<(u32, u32), ImageProcessingError> {
+    let context = libheif_rs::HeifContext::read_from_file(path).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    Ok((handle.width(), handle.height()))
+}
+
+#[cfg(not(feature = "heic"))]
+fn open_heif(path: &str) -> Result<DynamicImage, ImageProcessingError> {
+    Err(unsupported_format(path))
+}
+
+#[cfg(not(feature = "heic"))]
+fn heif_dimensions(path: &str) -> Result<(u32, u32), ImageProcessingError> {
+    Err(unsupported_format(path))
+}
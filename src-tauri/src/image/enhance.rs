@@ -0,0 +1,250 @@
+//! Classic (non-ML) exposure correction, used when the enhancement model isn't available.
+
+use image::{DynamicImage, Rgb32FImage};
+
+use crate::image::color::{lab_to_rgb, match_color_type, rgb_to_lab};
+
+/// Fraction of the darkest and brightest samples clipped by auto levels.
+const LEVELS_CLIP: f32 = 0.005;
+/// Maximum per-channel gain applied by the white balance.
+const MAX_WB_GAIN: f32 = 2.0;
+const CLAHE_TILES: usize = 8;
+const CLAHE_CLIP_LIMIT: f32 = 2.0;
+const BINS: usize = 256;
+
+/// [`auto_enhance`] on any image, keeping its alpha channel and bit depth.
+pub fn auto_enhance_image(image: &DynamicImage, intensity: f32) -> DynamicImage {
+    let enhanced = auto_enhance(&image.to_rgb32f(), intensity);
+    let enhanced = if image.color().has_alpha() {
+        let mut rgba = image.to_rgba32f();
+        for (pixel, enhanced) in rgba.pixels_mut().zip(enhanced.pixels()) {
+            pixel.0[..3].copy_from_slice(&enhanced.0);
+        }
+        DynamicImage::ImageRgba32F(rgba)
+    } else {
+        DynamicImage::ImageRgb32F(enhanced)
+    };
+
+    match_color_type(enhanced, image)
+}
+
+/// White balance, auto levels and CLAHE on luminance, blended with the input by `intensity`.
+/// Samples are clamped to `0.0..=1.0`.
+pub fn auto_enhance(image: &Rgb32FImage, intensity: f32) -> Rgb32FImage {
+    let mut enhanced = image.clone();
+    for pixel in enhanced.pixels_mut() {
+        pixel.0 = pixel.0.map(|v| v.clamp(0.0, 1.0));
+    }
+    white_balance(&mut enhanced);
+    auto_levels(&mut enhanced);
+    clahe_luminance(&mut enhanced);
+
+    let intensity = intensity.clamp(0.0, 1.0);
+    for (enhanced, original) in enhanced.pixels_mut().zip(image.pixels()) {
+        for c in 0..3 {
+            let (from, to) = (original[c].clamp(0.0, 1.0), enhanced[c]);
+            enhanced[c] = (from + (to - from) * intensity).clamp(0.0, 1.0);
+        }
+    }
+
+    enhanced
+}
+
+/// Histogram bin of a sample in `0.0..=1.0`.
+fn sample_bin(value: f32) -> usize {
+    ((value * (BINS - 1) as f32).round() as usize).min(BINS - 1)
+}
+
+/// Gray-world white balance: scales each channel so the channel means match.
+fn white_balance(image: &mut Rgb32FImage) {
+    let mut sums = [0f64; 3];
+    for pixel in image.pixels() {
+        for c in 0..3 {
+            sums[c] += pixel[c] as f64;
+        }
+    }
+    let gray = (sums[0] + sums[1] + sums[2]) / 3.0;
+    if gray == 0.0 {
+        return;
+    }
+
+    let gains = sums.map(|sum| {
+        if sum == 0.0 {
+            1.0
+        } else {
+            ((gray / sum) as f32).clamp(1.0 / MAX_WB_GAIN, MAX_WB_GAIN)
+        }
+    });
+    for pixel in image.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = (pixel[c] * gains[c]).clamp(0.0, 1.0);
+        }
+    }
+}
+
+/// Stretches the range between the clipped black and white points to the full `0.0..=1.0`,
+/// using the same points for all channels so colors don't shift.
+fn auto_levels(image: &mut Rgb32FImage) {
+    let mut histogram = [0usize; BINS];
+    for pixel in image.pixels() {
+        for c in 0..3 {
+            histogram[sample_bin(pixel[c])] += 1;
+        }
+    }
+
+    let total: usize = histogram.iter().sum();
+    let clip = (total as f32 * LEVELS_CLIP) as usize;
+    let low = clipped_bin(&histogram, clip, 0..BINS);
+    let high = clipped_bin(&histogram, clip, (0..BINS).rev());
+    if high <= low {
+        return;
+    }
+
+    let scale = (BINS - 1) as f32 / (high - low) as f32;
+    let low = low as f32 / (BINS - 1) as f32;
+    for pixel in image.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = ((pixel[c] - low) * scale).clamp(0.0, 1.0);
+        }
+    }
+}
+
+/// First bin, in iteration order, past the `clip` samples at that end of the histogram.
+fn clipped_bin(histogram: &[usize], clip: usize, mut bins: impl Iterator<Item = usize>) -> usize {
+    let mut count = 0;
+    bins.find(|&bin| {
+        count += histogram[bin];
+        count > clip
+    })
+    .unwrap_or(0)
+}
+
+/// Contrast limited adaptive histogram equalization on the Lab lightness, leaving the color
+/// channels untouched.
+fn clahe_luminance(image: &mut Rgb32FImage) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let mut lab: Vec<[f32; 3]> = image.pixels().map(|p| rgb_to_lab(p.0)).collect();
+    let bin = |l: f32| ((l / 100.0 * (BINS - 1) as f32).round() as usize).min(BINS - 1);
+
+    let tiles_x = CLAHE_TILES.min(width);
+    let tiles_y = CLAHE_TILES.min(height);
+    let tile_width = width.div_ceil(tiles_x);
+    let tile_height = height.div_ceil(tiles_y);
+
+    // Equalization mapping of every tile, from lightness bin to output bin
+    let mut mappings = vec![[0f32; BINS]; tiles_x * tiles_y];
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let mut histogram = [0f32; BINS];
+            let mut count = 0f32;
+            for y in ty * tile_height..((ty + 1) * tile_height).min(height) {
+                for x in tx * tile_width..((tx + 1) * tile_width).min(width) {
+                    histogram[bin(lab[y * width + x][0])] += 1.0;
+                    count += 1.0;
+                }
+            }
+            if count == 0.0 {
+                continue;
+            }
+
+            // Clip the histogram and spread the excess evenly to limit noise amplification
+            let limit = (CLAHE_CLIP_LIMIT * count / BINS as f32).max(1.0);
+            let mut excess = 0.0;
+            for value in histogram.iter_mut() {
+                if *value > limit {
+                    excess += *value - limit;
+                    *value = limit;
+                }
+            }
+            let share = excess / BINS as f32;
+
+            let mapping = &mut mappings[ty * tiles_x + tx];
+            let mut cdf = 0.0;
+            for (i, value) in histogram.iter().enumerate() {
+                cdf += value + share;
+                mapping[i] = cdf / count * (BINS - 1) as f32;
+            }
+        }
+    }
+
+    // Interpolate bilinearly between the mappings of the four nearest tile centers
+    let position = |coordinate: usize, size: usize, tiles: usize| {
+        let t = ((coordinate as f32 + 0.5) / size as f32 - 0.5).max(0.0);
+        let index = (t as usize).min(tiles - 1);
+        (index, (index + 1).min(tiles - 1), t - index as f32)
+    };
+    for y in 0..height {
+        let (ty0, ty1, fy) = position(y, tile_height, tiles_y);
+        for x in 0..width {
+            let (tx0, tx1, fx) = position(x, tile_width, tiles_x);
+            let l = &mut lab[y * width + x][0];
+            let b = bin(*l);
+            let map = |tx: usize, ty: usize| mappings[ty * tiles_x + tx][b];
+
+            let top = map(tx0, ty0) + (map(tx1, ty0) - map(tx0, ty0)) * fx;
+            let bottom = map(tx0, ty1) + (map(tx1, ty1) - map(tx0, ty1)) * fx;
+            *l = (top + (bottom - top) * fy) / (BINS - 1) as f32 * 100.0;
+        }
+    }
+
+    for (pixel, lab) in image.pixels_mut().zip(lab) {
+        *pixel = image::Rgb(lab_to_rgb(lab).map(|v| v.clamp(0.0, 1.0)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> Rgb32FImage {
+        Rgb32FImage::from_fn(16, 16, |x, y| {
+            image::Rgb([0.2 + x as f32 / 60.0, 0.25 + y as f32 / 60.0, 0.3])
+        })
+    }
+
+    #[test]
+    fn stretches_a_dim_image_to_the_full_range() {
+        let enhanced = auto_enhance(&gradient(), 1.0);
+        let (min, max) = enhanced
+            .pixels()
+            .flat_map(|pixel| pixel.0)
+            .fold((1f32, 0f32), |(min, max), v| (min.min(v), max.max(v)));
+        assert!(min < 0.1 && max > 0.9, "range {}..{}", min, max);
+    }
+
+    #[test]
+    fn zero_intensity_keeps_the_input() {
+        let input = gradient();
+        let enhanced = auto_enhance(&input, 0.0);
+        for (a, b) in enhanced.pixels().zip(input.pixels()) {
+            for c in 0..3 {
+                assert!((a[c] - b[c]).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_alpha_and_bit_depth() {
+        let rgba16 = image::ImageBuffer::<image::Rgba<u16>, _>::from_fn(16, 16, |x, y| {
+            image::Rgba([x as u16 * 1000, y as u16 * 1000, 12_000, 40_000 + x as u16])
+        });
+        let enhanced = auto_enhance_image(&DynamicImage::ImageRgba16(rgba16.clone()), 1.0);
+
+        let DynamicImage::ImageRgba16(enhanced) = enhanced else {
+            panic!("expected a 16-bit RGBA result, got {:?}", enhanced.color());
+        };
+        for (a, b) in enhanced.pixels().zip(rgba16.pixels()) {
+            assert_eq!(a[3], b[3]);
+        }
+
+        let rgb8 = DynamicImage::ImageRgb8(image::RgbImage::new(4, 4));
+        assert_eq!(
+            auto_enhance_image(&rgb8, 1.0).color(),
+            image::ColorType::Rgb8
+        );
+    }
+}
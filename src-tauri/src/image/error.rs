@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use super::validate::ValidationError;
+
 #[derive(Error, Debug)]
 pub enum ImageProcessingError {
     #[error("IO error: {0}")]
@@ -10,6 +12,12 @@ pub enum ImageProcessingError {
     Processing(String),
     #[error("Image error: {0}")]
     Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error("Model {model} was not found at {path}")]
+    ModelNotFound { model: String, path: String },
+    #[error("Model {model} could not be loaded: {message}")]
+    ModelLoad { model: String, message: String },
 }
 
 impl From<ort::Error> for ImageProcessingError {
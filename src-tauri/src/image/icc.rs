@@ -0,0 +1,280 @@
+//! ICC color management. The models are trained on sRGB, so images with another embedded profile
+//! (e.g. Display P3 or Adobe RGB) are converted to sRGB when opened, and can be converted back
+//! to their profile when the result is saved.
+
+use flate2::{write::ZlibEncoder, Compression};
+use image::{DynamicImage, ImageReader};
+use lcms2::{Flags, InfoType, Intent, Locale, PixelFormat, Pod, Profile, Transform};
+use std::io::Write;
+use tracing::{info, warn};
+
+use crate::image::decode::is_heif;
+use crate::image::error::ImageProcessingError;
+
+/// Largest profile chunk that fits in a JPEG APP2 segment next to its header.
+const JPEG_ICC_CHUNK: usize = 65519;
+const JPEG_ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+/// Returns the ICC profile embedded in the image at `path`, if any.
+pub fn read_profile(path: &str) -> Option<Vec<u8>> {
+    if is_heif(path) {
+        return heif_profile(path);
+    }
+
+    let mut decoder = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    image::ImageDecoder::icc_profile(&mut decoder)
+        .ok()
+        .flatten()
+}
+
+#[cfg(feature = "heic")]
+fn heif_profile(path: &str) -> Option<Vec<u8>> {
+    let context = libheif_rs::HeifContext::read_from_file(path).ok()?;
+    let handle = context.primary_image_handle().ok()?;
+    handle.color_profile_raw().map(|profile| profile.data)
+}
+
+#[cfg(not(feature = "heic"))]
+fn heif_profile(_path: &str) -> Option<Vec<u8>> {
+    None
+}
+
+fn cms_error(e: lcms2::Error) -> ImageProcessingError {
+    ImageProcessingError::Processing(format!("Color conversion failed: {}", e))
+}
+
+/// Parses an RGB profile, returning `None` for sRGB (nothing to convert) and profiles that can't
+/// be used, which are then treated as sRGB.
+fn rgb_profile(icc: &[u8]) -> Option<Profile> {
+    let profile = match Profile::new_icc(icc) {
+        Ok(profile) => profile,
+        Err(e) => {
+            warn!("Ignoring invalid ICC profile: {}", e);
+            return None;
+        }
+    };
+    if profile.color_space() != lcms2::ColorSpaceSignature::RgbData {
+        return None;
+    }
+
+    let description = profile
+        .info(InfoType::Description, Locale::none())
+        .unwrap_or_default();
+    if description.contains("sRGB") {
+        return None;
+    }
+    info!("Converting colors of ICC profile \"{}\"", description);
+    Some(profile)
+}
+
+/// Converts `image` from the `icc` profile to sRGB. Images keep their sample type.
+pub fn to_srgb(mut image: DynamicImage, icc: &[u8]) -> Result<DynamicImage, ImageProcessingError> {
+    if let Some(profile) = rgb_profile(icc) {
+        convert(&mut image, &profile, &Profile::new_srgb())?;
+    }
+    Ok(image)
+}
+
+/// Converts `image` from sRGB back to the `icc` profile.
+pub fn from_srgb(
+    mut image: DynamicImage,
+    icc: &[u8],
+) -> Result<DynamicImage, ImageProcessingError> {
+    if let Some(profile) = rgb_profile(icc) {
+        convert(&mut image, &Profile::new_srgb(), &profile)?;
+    }
+    Ok(image)
+}
+
+fn convert(
+    image: &mut DynamicImage,
+    from: &Profile,
+    to: &Profile,
+) -> Result<(), ImageProcessingError> {
+    match image {
+        DynamicImage::ImageRgb8(buffer) => {
+            transform::<[u8; 3], _>(buffer, from, to, PixelFormat::RGB_8)
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            transform::<[u8; 4], _>(buffer, from, to, PixelFormat::RGBA_8)
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            transform::<[u16; 3], _>(buffer, from, to, PixelFormat::RGB_16)
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            transform::<[u16; 4], _>(buffer, from, to, PixelFormat::RGBA_16)
+        }
+        DynamicImage::ImageRgb32F(buffer) => {
+            transform::<[f32; 3], _>(buffer, from, to, PixelFormat::RGB_FLT)
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            transform::<[f32; 4], _>(buffer, from, to, PixelFormat::RGBA_FLT)
+        }
+        // Grayscale images don't carry RGB profiles
+        _ => Ok(()),
+    }
+}
+
+fn transform<Pixel: Pod, Sample: Pod>(
+    samples: &mut [Sample],
+    from: &Profile,
+    to: &Profile,
+    format: PixelFormat,
+) -> Result<(), ImageProcessingError> {
+    let transform = Transform::<Pixel, Pixel>::new_flags(
+        from,
+        format,
+        to,
+        format,
+        Intent::Perceptual,
+        Flags::COPY_ALPHA,
+    )
+    .map_err(cms_error)?;
+    transform.transform_in_place(bytemuck::cast_slice_mut(samples));
+    Ok(())
+}
+
+/// Inserts `icc` into an encoded PNG as an `iCCP` chunk right after the header.
+pub(crate) fn embed_in_png(png: &[u8], icc: &[u8]) -> Result<Vec<u8>, ImageProcessingError> {
+    // Signature (8 bytes), then IHDR: length, type, 13 bytes of data and the CRC
+    const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < IHDR_END || &png[12..16] != b"IHDR" {
+        return Err(ImageProcessingError::Processing(
+            "Invalid PNG output".to_string(),
+        ));
+    }
+
+    let mut data = b"ICC Profile\0\0".to_vec();
+    let mut encoder = ZlibEncoder::new(&mut data, Compression::default());
+    encoder.write_all(icc)?;
+    encoder.finish()?;
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(b"iCCP");
+    chunk.extend_from_slice(&data);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+
+    let mut output = Vec::with_capacity(png.len() + chunk.len());
+    output.extend_from_slice(&png[..IHDR_END]);
+    output.extend_from_slice(&chunk);
+    output.extend_from_slice(&png[IHDR_END..]);
+    Ok(output)
+}
+
+/// Inserts `icc` into an encoded JPEG as `APP2` segments after the JFIF header.
+pub(crate) fn embed_in_jpeg(jpeg: &[u8], icc: &[u8]) -> Result<Vec<u8>, ImageProcessingError> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return Err(ImageProcessingError::Processing(
+            "Invalid JPEG output".to_string(),
+        ));
+    }
+    // Keep the APP0 (JFIF) segment first, as some readers expect
+    let mut insert_at = 2;
+    if let (Some(&[0xFF, 0xE0]), Some(&[high, low])) = (jpeg.get(2..4), jpeg.get(4..6)) {
+        insert_at = (2 + 2 + u16::from_be_bytes([high, low]) as usize).min(jpeg.len());
+    }
+
+    let chunks: Vec<&[u8]> = icc.chunks(JPEG_ICC_CHUNK).collect();
+    // Chunks are numbered with one byte
+    let count = u8::try_from(chunks.len()).map_err(|_| {
+        ImageProcessingError::Processing(format!(
+            "The ICC profile is too large to embed in a JPEG ({} bytes)",
+            icc.len()
+        ))
+    })?;
+    let mut output = Vec::with_capacity(jpeg.len() + icc.len() + chunks.len() * 18);
+    output.extend_from_slice(&jpeg[..insert_at]);
+    for (sequence, chunk) in (1..=count).zip(&chunks) {
+        // The length covers itself, the marker, the sequence number, the count and the data
+        let length = 2 + JPEG_ICC_MARKER.len() + 2 + chunk.len();
+        output.extend_from_slice(&[0xFF, 0xE2]);
+        output.extend_from_slice(&(length as u16).to_be_bytes());
+        output.extend_from_slice(JPEG_ICC_MARKER);
+        output.push(sequence);
+        output.push(count);
+        output.extend_from_slice(chunk);
+    }
+    output.extend_from_slice(&jpeg[insert_at..]);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageDecoder;
+    use std::io::Cursor;
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let image =
+            image::RgbImage::from_fn(16, 8, |x, y| image::Rgb([x as u8 * 16, y as u8 * 32, 0]));
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    /// A profile-sized byte pattern; the decoders only hand the bytes back.
+    fn profile(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn png_profile(png: &[u8]) -> Option<Vec<u8>> {
+        image::codecs::png::PngDecoder::new(Cursor::new(png))
+            .unwrap()
+            .icc_profile()
+            .unwrap()
+    }
+
+    fn jpeg_profile(jpeg: &[u8]) -> Option<Vec<u8>> {
+        image::codecs::jpeg::JpegDecoder::new(Cursor::new(jpeg))
+            .unwrap()
+            .icc_profile()
+            .unwrap()
+    }
+
+    #[test]
+    fn png_profiles_round_trip() {
+        let icc = Profile::new_srgb().icc().unwrap();
+        let png = embed_in_png(&encoded(image::ImageFormat::Png), &icc).unwrap();
+
+        assert_eq!(png_profile(&png), Some(icc));
+        assert!(image::load_from_memory(&png).is_ok());
+    }
+
+    #[test]
+    fn jpeg_profiles_round_trip() {
+        let icc = Profile::new_srgb().icc().unwrap();
+        let jpeg = embed_in_jpeg(&encoded(image::ImageFormat::Jpeg), &icc).unwrap();
+
+        assert_eq!(jpeg_profile(&jpeg), Some(icc));
+        assert!(image::load_from_memory(&jpeg).is_ok());
+    }
+
+    #[test]
+    fn large_jpeg_profiles_are_split_into_chunks() {
+        let icc = profile(2 * JPEG_ICC_CHUNK + 100);
+        let jpeg = embed_in_jpeg(&encoded(image::ImageFormat::Jpeg), &icc).unwrap();
+
+        let segments = jpeg
+            .windows(2 + 2 + JPEG_ICC_MARKER.len())
+            .filter(|window| window[..2] == [0xFF, 0xE2] && &window[4..] == JPEG_ICC_MARKER)
+            .count();
+        assert_eq!(segments, 3);
+        assert_eq!(jpeg_profile(&jpeg), Some(icc));
+    }
+
+    #[test]
+    fn jpeg_profiles_beyond_255_chunks_are_rejected() {
+        let jpeg = encoded(image::ImageFormat::Jpeg);
+
+        assert!(embed_in_jpeg(&jpeg, &profile(255 * JPEG_ICC_CHUNK)).is_ok());
+        assert!(embed_in_jpeg(&jpeg, &profile(255 * JPEG_ICC_CHUNK + 1)).is_err());
+    }
+}
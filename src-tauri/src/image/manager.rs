@@ -0,0 +1,192 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use super::{processor::ModelProcessor, ImageModel, ImageProcessingError};
+use crate::utils::models_dir;
+
+/// Holds the processor of one model and controls when it is loaded and freed.
+///
+/// Jobs receive an `Arc` of the processor, so a reload or unload never pulls a session out from
+/// under a running job: the old session is freed once its last in-flight job finishes.
+pub struct ModelSlot<M: ImageModel + Send + Sync> {
+    name: &'static str,
+    file_name: &'static str,
+    state: Mutex<SlotState<M>>,
+    /// Held while the model loads, so `state` stays free for status queries meanwhile and
+    /// concurrent first uses load it only once.
+    loading: Mutex<()>,
+}
+
+struct SlotState<M: ImageModel + Send + Sync> {
+    processor: Option<Arc<ModelProcessor<M>>>,
+    model_size: u64,
+    last_used: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelStatus {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub loaded: bool,
+    pub in_flight: usize,
+    /// Size of the loaded model file, as an estimate of the memory held by its weights.
+    pub memory_bytes: u64,
+    pub idle_seconds: Option<u64>,
+}
+
+/// Object-safe view of a [`ModelSlot`], so slots of different model types can be listed together.
+pub trait ManagedModel: Sync {
+    fn name(&self) -> &'static str;
+    fn is_available(&self) -> bool;
+    fn load(&self) -> Result<(), ImageProcessingError>;
+    fn reload(&self) -> Result<(), ImageProcessingError>;
+    fn unload(&self) -> bool;
+    fn unload_if_idle(&self, timeout: Duration) -> bool;
+    fn status(&self) -> ModelStatus;
+}
+
+impl<M: ImageModel + Send + Sync> ModelSlot<M> {
+    pub const fn new(name: &'static str, file_name: &'static str) -> Self {
+        Self {
+            name,
+            file_name,
+            state: Mutex::new(SlotState {
+                processor: None,
+                model_size: 0,
+                last_used: None,
+            }),
+            loading: Mutex::new(()),
+        }
+    }
+
+    /// Returns the processor, loading the model on first use.
+    pub fn get(&self) -> Result<Arc<ModelProcessor<M>>, ImageProcessingError> {
+        if let Some(processor) = self.loaded() {
+            return Ok(processor);
+        }
+
+        let _loading = self.loading.lock().unwrap();
+        // Another caller may have loaded the model while this one waited
+        if let Some(processor) = self.loaded() {
+            return Ok(processor);
+        }
+
+        let (processor, model_size) = self.create()?;
+        let mut state = self.state.lock().unwrap();
+        state.processor = Some(processor.clone());
+        state.model_size = model_size;
+        state.last_used = Some(Instant::now());
+        Ok(processor)
+    }
+
+    /// Returns the processor if the model is loaded, marking it as used.
+    fn loaded(&self) -> Option<Arc<ModelProcessor<M>>> {
+        let mut state = self.state.lock().unwrap();
+        state.last_used = Some(Instant::now());
+        state.processor.clone()
+    }
+
+    /// Whether the model is loaded or its file has been downloaded.
+    pub fn is_available(&self) -> bool {
+        self.state.lock().unwrap().processor.is_some() || self.model_path().exists()
+    }
+
+    pub fn model_path(&self) -> PathBuf {
+        models_dir().join(self.file_name)
+    }
+
+    fn create(&self) -> Result<(Arc<ModelProcessor<M>>, u64), ImageProcessingError> {
+        let model_path = self.model_path();
+        if !model_path.exists() {
+            return Err(ImageProcessingError::ModelNotFound {
+                model: self.name.to_string(),
+                path: model_path.display().to_string(),
+            });
+        }
+        info!("Loading model {} from {}", self.name, model_path.display());
+
+        let processor = ModelProcessor::<M>::new(model_path.to_str().unwrap()).map_err(|e| {
+            ImageProcessingError::ModelLoad {
+                model: self.name.to_string(),
+                message: e.to_string(),
+            }
+        })?;
+        let model_size = std::fs::metadata(&model_path).map_or(0, |m| m.len());
+        Ok((Arc::new(processor), model_size))
+    }
+}
+
+impl<M: ImageModel + Send + Sync> ManagedModel for ModelSlot<M> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn is_available(&self) -> bool {
+        ModelSlot::is_available(self)
+    }
+
+    fn load(&self) -> Result<(), ImageProcessingError> {
+        self.get().map(|_| ())
+    }
+
+    fn reload(&self) -> Result<(), ImageProcessingError> {
+        // Build the new session before swapping so jobs keep being served in the meantime
+        let (processor, model_size) = self.create()?;
+
+        let mut state = self.state.lock().unwrap();
+        state.processor = Some(processor);
+        state.model_size = model_size;
+        state.last_used = Some(Instant::now());
+        info!("Reloaded model {}", self.name);
+        Ok(())
+    }
+
+    fn unload(&self) -> bool {
+        let unloaded = self.state.lock().unwrap().processor.take().is_some();
+        if unloaded {
+            info!("Unloaded model {}", self.name);
+        }
+        unloaded
+    }
+
+    fn unload_if_idle(&self, timeout: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let idle = state
+            .last_used
+            .is_some_and(|last_used| last_used.elapsed() >= timeout);
+        let in_flight = state
+            .processor
+            .as_ref()
+            .map_or(0, |processor| Arc::strong_count(processor) - 1);
+
+        if !idle || in_flight > 0 || state.processor.take().is_none() {
+            return false;
+        }
+
+        info!("Unloaded idle model {}", self.name);
+        true
+    }
+
+    fn status(&self) -> ModelStatus {
+        let state = self.state.lock().unwrap();
+        let loaded = state.processor.is_some();
+
+        ModelStatus {
+            name: self.name,
+            file_name: self.file_name,
+            loaded,
+            in_flight: state
+                .processor
+                .as_ref()
+                .map_or(0, |processor| Arc::strong_count(processor) - 1),
+            memory_bytes: if loaded { state.model_size } else { 0 },
+            idle_seconds: state
+                .last_used
+                .filter(|_| loaded)
+                .map(|last_used| last_used.elapsed().as_secs()),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Compositing of segmentation masks onto images.
+
+use image::{DynamicImage, GenericImageView, GrayImage};
+
+/// Combines the colors of `original` with `mask` as alpha, resizing the mask to the image first.
+/// 16-bit and floating point originals keep their precision.
+pub fn apply_mask(original: &DynamicImage, mask: &GrayImage) -> DynamicImage {
+    // Ensure original image and mask have same dimensions
+    let mask = if mask.dimensions() == original.dimensions() {
+        mask.clone()
+    } else {
+        image::imageops::resize(
+            mask,
+            original.width(),
+            original.height(),
+            image::imageops::FilterType::Triangle,
+        )
+    };
+
+    // Create final image by combining original colors with mask
+    match original {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_) => {
+            let mut final_image = original.to_rgba8();
+            for (pixel, mask_pixel) in final_image.pixels_mut().zip(mask.pixels()) {
+                pixel[3] = mask_pixel[0];
+            }
+            DynamicImage::ImageRgba8(final_image)
+        }
+        _ => {
+            let mut final_image = original.to_rgba32f();
+            for (pixel, mask_pixel) in final_image.pixels_mut().zip(mask.pixels()) {
+                pixel[3] = mask_pixel[0] as f32 / 255.0;
+            }
+            DynamicImage::ImageRgba32F(final_image)
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Image quality metrics, for catching regressions in model output and for showing how much a
+//! result differs from its input.
+
+use image::{DynamicImage, GenericImageView};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::image::error::ImageProcessingError;
+
+/// Gaussian window of the SSIM paper (11x11, sigma 1.5), with its constants for a `[0, 1]` range.
+const SSIM_RADIUS: usize = 5;
+const SSIM_SIGMA: f32 = 1.5;
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+/// Alpha above which a pixel counts as foreground.
+const ALPHA_THRESHOLD: f32 = 0.5;
+
+/// How similar two images are.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ImageMetrics {
+    /// Peak signal-to-noise ratio in dB, or `None` if the images are identical.
+    pub psnr: Option<f64>,
+    /// Structural similarity of the luminance, from -1 to 1.
+    pub ssim: f64,
+    /// Intersection over union of the foreground masks, if both images have alpha.
+    pub alpha_iou: Option<f64>,
+}
+
+fn check_dimensions(a: &DynamicImage, b: &DynamicImage) -> Result<(), ImageProcessingError> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(ImageProcessingError::Processing(format!(
+            "Images have different sizes: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+    Ok(())
+}
+
+/// Computes all metrics between `reference` and `candidate`, which must have the same size.
+pub fn compare(
+    reference: &DynamicImage,
+    candidate: &DynamicImage,
+) -> Result<ImageMetrics, ImageProcessingError> {
+    let psnr = psnr(reference, candidate)?;
+    let alpha_iou = if reference.color().has_alpha() && candidate.color().has_alpha() {
+        Some(alpha_iou(reference, candidate)?)
+    } else {
+        None
+    };
+
+    Ok(ImageMetrics {
+        psnr: psnr.is_finite().then_some(psnr),
+        ssim: ssim(reference, candidate)?,
+        alpha_iou,
+    })
+}
+
+/// Like [`compare`], but first resamples a `reference` of another size to the size of
+/// `candidate`, so results that change the size (e.g. upscaling) can be compared with their input.
+pub fn compare_resized(
+    reference: &DynamicImage,
+    candidate: &DynamicImage,
+) -> Result<ImageMetrics, ImageProcessingError> {
+    if reference.dimensions() == candidate.dimensions() {
+        return compare(reference, candidate);
+    }
+    let resized = reference.resize_exact(
+        candidate.width(),
+        candidate.height(),
+        image::imageops::FilterType::Lanczos3,
+    );
+    compare(&resized, candidate)
+}
+
+/// Peak signal-to-noise ratio over the RGB channels in dB; infinite for identical images.
+pub fn psnr(a: &DynamicImage, b: &DynamicImage) -> Result<f64, ImageProcessingError> {
+    check_dimensions(a, b)?;
+    let (a, b) = (a.to_rgb32f(), b.to_rgb32f());
+
+    let squared_error: f64 = a
+        .as_raw()
+        .par_iter()
+        .zip(b.as_raw().par_iter())
+        .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+        .sum();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+
+    Ok(10.0 * (1.0 / mse).log10())
+}
+
+/// Mean structural similarity of the luminance.
+pub fn ssim(a: &DynamicImage, b: &DynamicImage) -> Result<f64, ImageProcessingError> {
+    check_dimensions(a, b)?;
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let (a, b) = (luminance(a), luminance(b));
+
+    let kernel = gaussian_kernel();
+    let smooth = |values: Vec<f32>| blur(&values, width, height, &kernel);
+    let mean_a = smooth(a.clone());
+    let mean_b = smooth(b.clone());
+    let mean_aa = smooth(a.iter().map(|x| x * x).collect());
+    let mean_bb = smooth(b.iter().map(|y| y * y).collect());
+    let mean_ab = smooth(a.iter().zip(&b).map(|(x, y)| x * y).collect());
+
+    let total: f64 = (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let (mu_a, mu_b) = (mean_a[i], mean_b[i]);
+            let variance_a = mean_aa[i] - mu_a * mu_a;
+            let variance_b = mean_bb[i] - mu_b * mu_b;
+            let covariance = mean_ab[i] - mu_a * mu_b;
+
+            let similarity = ((2.0 * mu_a * mu_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mu_a * mu_a + mu_b * mu_b + SSIM_C1) * (variance_a + variance_b + SSIM_C2));
+            similarity as f64
+        })
+        .sum();
+
+    Ok(total / (width * height).max(1) as f64)
+}
+
+/// Intersection over union of the pixels whose alpha is above one half, as used to score
+/// background removal masks. Two empty masks count as identical.
+pub fn alpha_iou(a: &DynamicImage, b: &DynamicImage) -> Result<f64, ImageProcessingError> {
+    check_dimensions(a, b)?;
+    let (a, b) = (a.to_rgba32f(), b.to_rgba32f());
+
+    let (intersection, union) = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(x, y)| (x[3] > ALPHA_THRESHOLD, y[3] > ALPHA_THRESHOLD))
+        .fold((0u64, 0u64), |(intersection, union), (x, y)| {
+            (intersection + (x && y) as u64, union + (x || y) as u64)
+        });
+
+    Ok(if union == 0 {
+        1.0
+    } else {
+        intersection as f64 / union as f64
+    })
+}
+
+/// Rec. 601 luma in `[0, 1]`.
+fn luminance(image: &DynamicImage) -> Vec<f32> {
+    image
+        .to_rgb32f()
+        .pixels()
+        .map(|pixel| 0.299 * pixel[0] + 0.587 * pixel[1] + 0.114 * pixel[2])
+        .collect()
+}
+
+fn gaussian_kernel() -> Vec<f32> {
+    let weights: Vec<f32> = (0..=2 * SSIM_RADIUS)
+        .map(|i| {
+            let offset = i as f32 - SSIM_RADIUS as f32;
+            (-offset * offset / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp()
+        })
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.iter().map(|weight| weight / sum).collect()
+}
+
+/// Separable blur with `kernel`, repeating the edge pixels.
+fn blur(values: &[f32], width: usize, height: usize, kernel: &[f32]) -> Vec<f32> {
+    let radius = kernel.len() / 2;
+    let tap = |i: usize, k: usize, len: usize| (i + k).saturating_sub(radius).min(len - 1);
+
+    let mut horizontal = vec![0.0; values.len()];
+    horizontal
+        .par_chunks_mut(width)
+        .zip(values.par_chunks(width))
+        .for_each(|(output, row)| {
+            for (x, value) in output.iter_mut().enumerate() {
+                *value = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| weight * row[tap(x, k, width)])
+                    .sum();
+            }
+        });
+
+    let mut output = vec![0.0; values.len()];
+    output
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| weight * horizontal[tap(y, k, height) * width + x])
+                    .sum();
+            }
+        });
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8])
+        }))
+    }
+
+    fn mask(width: u32, height: u32, inside: impl Fn(u32, u32) -> bool) -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([0, 0, 0, if inside(x, y) { 255 } else { 0 }])
+        }))
+    }
+
+    #[test]
+    fn resized_comparison_accepts_other_sizes() {
+        let input = gradient(16, 12);
+        let upscaled = input.resize_exact(64, 48, image::imageops::FilterType::Triangle);
+
+        assert!(compare(&input, &upscaled).is_err());
+        let metrics = compare_resized(&input, &upscaled).unwrap();
+        assert!(metrics.ssim > 0.9, "SSIM {}", metrics.ssim);
+        assert!(metrics.psnr.is_some_and(|psnr| psnr > 25.0));
+    }
+
+    #[test]
+    fn identical_images_match_perfectly() {
+        let image = gradient(24, 16);
+
+        let metrics = compare(&image, &image).unwrap();
+        assert_eq!(metrics.psnr, None);
+        assert!((metrics.ssim - 1.0).abs() < 1e-6);
+        assert_eq!(metrics.alpha_iou, None);
+    }
+
+    #[test]
+    fn psnr_follows_the_mean_squared_error() {
+        let black = DynamicImage::ImageRgb32F(image::Rgb32FImage::new(4, 4));
+        let gray = DynamicImage::ImageRgb32F(image::Rgb32FImage::from_pixel(
+            4,
+            4,
+            image::Rgb([0.1, 0.1, 0.1]),
+        ));
+
+        // An error of 0.1 everywhere is an MSE of 0.01, or 20 dB
+        assert!((psnr(&black, &gray).unwrap() - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ssim_drops_with_noise() {
+        let image = gradient(32, 32);
+        let mut noisy = image.to_rgb8();
+        for (x, y, pixel) in noisy.enumerate_pixels_mut() {
+            if (x + y) % 2 == 0 {
+                pixel[0] = pixel[0].saturating_add(40);
+                pixel[1] = pixel[1].saturating_add(40);
+            }
+        }
+
+        let similarity = ssim(&image, &DynamicImage::ImageRgb8(noisy)).unwrap();
+        assert!(similarity < 0.9, "SSIM was {}", similarity);
+        assert!(similarity > 0.0);
+    }
+
+    #[test]
+    fn alpha_iou_compares_foreground_masks() {
+        let left = mask(10, 10, |x, _| x < 6);
+        let right = mask(10, 10, |x, _| x >= 4);
+
+        // Columns 4 and 5 overlap, out of all ten
+        assert!((alpha_iou(&left, &right).unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(alpha_iou(&left, &left).unwrap(), 1.0);
+        let empty = mask(10, 10, |_, _| false);
+        assert_eq!(alpha_iou(&empty, &empty).unwrap(), 1.0);
+        assert_eq!(compare(&left, &right).unwrap().alpha_iou, Some(0.2));
+    }
+
+    #[test]
+    fn different_sizes_are_rejected() {
+        assert!(compare(&gradient(8, 8), &gradient(8, 9)).is_err());
+    }
+}
@@ -1,8 +1,17 @@
+mod color;
+pub mod decode;
+pub mod enhance;
 mod error;
+pub mod icc;
+pub mod manager;
+pub mod mask;
+pub mod metrics;
 pub mod model;
 pub mod processor;
+pub mod session;
 mod tensor;
 pub mod types;
+pub mod validate;
 
 pub use error::ImageProcessingError;
 pub use model::ImageModel;
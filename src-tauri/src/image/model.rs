@@ -1,12 +1,29 @@
-use image::DynamicImage;
-use ort::{inputs, session::builder::GraphOptimizationLevel, session::Session, value::Value};
+use image::{DynamicImage, GenericImageView};
+use ort::{
+    inputs,
+    session::Session,
+    value::{DynValue, Value},
+};
+use rayon::prelude::*;
 use std::marker::PhantomData;
 
+use crate::image::color::{lab_to_rgb, match_color_type, rgb_to_lab};
+use crate::image::decode::{decode_bytes, open_image};
 use crate::image::error::ImageProcessingError;
-use crate::image::tensor::{image_to_tensor, tensor_to_image};
+use crate::image::mask::apply_mask;
+use crate::image::session::{build_session, SessionConfig};
+use crate::image::tensor::{
+    blended_tensor_to_image, denormalized_rgb8, image_to_tensor, interleaved_to_planar,
+    normalized_tensor, output_planes, padded_image_to_tensor, planar_from_rows,
+    planar_to_interleaved, tensor_to_image, Normalization,
+};
 use crate::image::types::{
-    FaceRestorationParams, NumericType, TensorInput, TensorOutput, UpscalingParams,
+    ArtifactRemovalInput, ArtifactRemovalParams, ColorizationParams, DenoiseParams, EnhanceParams,
+    FaceRestorationParams, InpaintingInput, InpaintingParams, MaskSource, NumericType,
+    PromptDecoderInput, PromptDecoderParams, PromptEncoderParams, SegmentationParams, TensorInput,
+    TensorOutput, UpscalingParams,
 };
+use crate::image::validate::validate;
 
 use super::types::BackgroundRemovalParams;
 
@@ -14,42 +31,62 @@ pub trait ImageModel {
     type Params: Sync + Clone;
     type InputType: NumericType;
     type OutputType: NumericType;
+    /// Everything fed to the session: a single tensor for most models, or a struct of tensors
+    /// for models with several inputs.
+    type Input;
+    /// How many times larger than the input the result is, for estimating memory use.
+    const OUTPUT_SCALE: u32 = 1;
 
-    fn load_session(model_path: &str) -> Result<Session, ImageProcessingError>;
+    fn load_session(
+        model_path: &str,
+        config: &SessionConfig,
+    ) -> Result<Session, ImageProcessingError> {
+        build_session(model_path, config)
+    }
     fn preprocess(
         image_path: &str,
         params: &mut Self::Params,
-    ) -> Result<TensorInput<Self::InputType>, ImageProcessingError>;
+    ) -> Result<Self::Input, ImageProcessingError>;
     fn postprocess(
         output: &TensorOutput<Self::OutputType>,
         params: &Self::Params,
     ) -> Result<DynamicImage, ImageProcessingError>;
+    /// Runs the model, taking `input` by value so ONNX Runtime can use its memory in place.
     fn process(
         session: &Session,
-        input: &TensorInput<Self::InputType>,
+        input: Self::Input,
     ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError>;
 }
 
+/// Copies a 4D model output into a tensor for postprocessing. ONNX Runtime owns the memory of
+/// its outputs, so this is the only copy on the way out.
+fn output_tensor<T: ort::tensor::PrimitiveTensorElementType + Clone>(
+    value: &DynValue,
+) -> Result<TensorOutput<T>, ImageProcessingError> {
+    let tensor = value.try_extract_tensor::<T>()?;
+    let shape = tensor.shape().to_vec();
+    tensor
+        .into_dimensionality::<ndarray::Ix4>()
+        .map(|tensor| tensor.to_owned())
+        .map_err(|_| {
+            ImageProcessingError::Processing(format!("Unexpected output shape {:?}", shape))
+        })
+}
+
 pub struct UpscalingModel<T = half::f16>(PhantomData<T>);
 
 impl ImageModel for UpscalingModel<half::f16> {
     type Params = UpscalingParams;
     type InputType = half::f16;
     type OutputType = half::f16;
-
-    fn load_session(model_path: &str) -> Result<Session, ImageProcessingError> {
-        Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)
-            .map_err(|e| ImageProcessingError::Ort(e.to_string()))
-    }
+    type Input = TensorInput<Self::InputType>;
+    const OUTPUT_SCALE: u32 = 4;
 
     fn preprocess(
         image_path: &str,
         _params: &mut Self::Params,
-    ) -> Result<TensorInput<Self::InputType>, ImageProcessingError> {
-        let image = image::open(image_path)?;
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
 
         // Make sure that the image size is even
         let width = if image.width() % 2 == 1 {
@@ -77,48 +114,35 @@ impl ImageModel for UpscalingModel<half::f16> {
 
     fn process(
         session: &Session,
-        input: &TensorInput<Self::InputType>,
+        input: Self::Input,
     ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
-        let input_array = input.clone().into_dyn();
-        let input_value = Value::from_array(input_array)?;
-        let inputs = inputs![input_value]?;
+        let inputs = inputs![Value::from_array(input)?]?;
 
         let outputs = session.run(inputs)?;
         let output = outputs
             .get("output")
             .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
 
-        let output_tensor = output.try_extract_tensor::<Self::InputType>()?;
-        let output_shape = output_tensor.shape();
-
-        ndarray::Array4::from_shape_vec(
-            (1, 3, output_shape[2], output_shape[3]),
-            output_tensor.as_slice().unwrap().to_vec(),
-        )
-        .map_err(|e| ImageProcessingError::Processing(e.to_string()))
+        output_tensor(output)
     }
 }
 
+/// Face restoration models take samples centered on 128 and divided by 256.
+const FACE_NORMALIZATION: Normalization = Normalization::uniform(1.0, 128.0, 256.0);
+
 pub struct FaceRestorationModel<T = f32>(PhantomData<T>);
 
 impl ImageModel for FaceRestorationModel<f32> {
     type Params = FaceRestorationParams;
     type InputType = f32;
     type OutputType = f32;
-
-    fn load_session(model_path: &str) -> Result<Session, ImageProcessingError> {
-        Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)
-            .map_err(|e| ImageProcessingError::Ort(e.to_string()))
-    }
+    type Input = TensorInput<Self::InputType>;
 
     fn preprocess(
         image_path: &str,
         params: &mut Self::Params,
-    ) -> Result<TensorInput<Self::InputType>, ImageProcessingError> {
-        let image = image::open(image_path)?;
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
 
         // Save original dimensions for postprocessing
         params.original_width = Some(image.width());
@@ -147,38 +171,14 @@ impl ImageModel for FaceRestorationModel<f32> {
             image::imageops::FilterType::Triangle,
         );
 
-        // Convert to tensor with normalization - now using 3 channels
-        let tensor =
-            ndarray::Array::from_shape_fn((1, 3, input_shape.0, input_shape.1), |(_, c, y, x)| {
-                let pixel = resized_img.get_pixel(x as u32, y as u32);
-                let mean = 128.0;
-                let std = 256.0;
-                (pixel[c] as f32 - mean) / std
-            });
-
-        Ok(tensor)
+        Ok(normalized_tensor(&resized_img, &FACE_NORMALIZATION))
     }
 
     fn postprocess(
         output: &TensorOutput<Self::OutputType>,
         params: &Self::Params,
     ) -> Result<DynamicImage, ImageProcessingError> {
-        let (_, _channels, height, width) = output.dim();
-        let mut img_buffer = image::RgbImage::new(width as u32, height as u32);
-
-        // Convert tensor values back to RGB
-        for y in 0..height {
-            for x in 0..width {
-                // Denormalize values back to 0-255 range
-                let r = ((output[[0, 0, y, x]] * 256.0) + 128.0).clamp(0.0, 255.0) as u8;
-                let g = ((output[[0, 1, y, x]] * 256.0) + 128.0).clamp(0.0, 255.0) as u8;
-                let b = ((output[[0, 2, y, x]] * 256.0) + 128.0).clamp(0.0, 255.0) as u8;
-
-                img_buffer.put_pixel(x as u32, y as u32, image::Rgb([r, g, b]));
-            }
-        }
-
-        let mut result = DynamicImage::ImageRgb8(img_buffer);
+        let mut result = DynamicImage::ImageRgb8(denormalized_rgb8(output, &FACE_NORMALIZATION)?);
 
         // Resize back to original dimensions if they exist
         if let (Some(original_width), Some(original_height)) =
@@ -196,11 +196,9 @@ impl ImageModel for FaceRestorationModel<f32> {
 
     fn process(
         session: &Session,
-        input: &TensorInput<Self::InputType>,
+        input: Self::Input,
     ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
-        let input_array = input.clone().into_dyn();
-        let input_value = Value::from_array(input_array)?;
-        let inputs = inputs![input_value]?;
+        let inputs = inputs![Value::from_array(input)?]?;
 
         let outputs = session.run(inputs)?;
         let output = outputs
@@ -208,36 +206,25 @@ impl ImageModel for FaceRestorationModel<f32> {
             .next()
             .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
 
-        let output_tensor = output.try_extract_tensor::<Self::InputType>()?;
-        let output_shape = output_tensor.shape();
-
-        ndarray::Array4::from_shape_vec(
-            (1, 3, output_shape[2], output_shape[3]),
-            output_tensor.as_slice().unwrap().to_vec(),
-        )
-        .map_err(|e| ImageProcessingError::Processing(e.to_string()))
+        output_tensor(&output)
     }
 }
 
+/// Background removal models take inputs in `[-1, 1]`.
+const BACKGROUND_REMOVAL_NORMALIZATION: Normalization = Normalization::uniform(255.0, 0.5, 0.5);
+
 pub struct BackgroundRemovalModel<T = f32>(PhantomData<T>);
 impl ImageModel for BackgroundRemovalModel<f32> {
     type Params = BackgroundRemovalParams;
     type InputType = f32;
     type OutputType = f32;
-
-    fn load_session(model_path: &str) -> Result<Session, ImageProcessingError> {
-        Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)
-            .map_err(|e| ImageProcessingError::Ort(e.to_string()))
-    }
+    type Input = TensorInput<Self::InputType>;
 
     fn preprocess(
         image_path: &str,
         params: &mut Self::Params,
-    ) -> Result<TensorInput<Self::InputType>, ImageProcessingError> {
-        let image = image::open(image_path)?;
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
 
         // Save original dimensions for postprocessing
         params.original_width = Some(image.width());
@@ -249,18 +236,14 @@ impl ImageModel for BackgroundRemovalModel<f32> {
             params.model_height as u32,
             image::imageops::FilterType::Lanczos3,
         );
+        if params.composite {
+            params.original = Some(image);
+        }
 
-        // Convert to tensor with normalization
-        let rgb_image = resized.to_rgb8();
-        let tensor = ndarray::Array::from_shape_fn(
-            (1, 3, params.model_height, params.model_width),
-            |(_, c, y, x)| {
-                let pixel = rgb_image.get_pixel(x as u32, y as u32);
-                (pixel[c] as f32 / 255.0 - 0.5) / 0.5
-            },
-        );
-
-        Ok(tensor)
+        Ok(normalized_tensor(
+            &resized.to_rgb8(),
+            &BACKGROUND_REMOVAL_NORMALIZATION,
+        ))
     }
 
     fn postprocess(
@@ -268,16 +251,26 @@ impl ImageModel for BackgroundRemovalModel<f32> {
         params: &Self::Params,
     ) -> Result<DynamicImage, ImageProcessingError> {
         let (_, _, height, width) = output.dim();
-        let mut img_buffer = image::RgbaImage::new(width as u32, height as u32);
+
+        let wrong_size = || ImageProcessingError::Processing("Mask has the wrong size".to_string());
 
         // Convert tensor values to alpha channel
-        for y in 0..height {
-            for x in 0..width {
-                let alpha = (output[[0, 0, y, x]] * 255.0).clamp(0.0, 255.0) as u8;
-                img_buffer.put_pixel(x as u32, y as u32, image::Rgba([0, 0, 0, alpha]));
-            }
+        let alpha = planar_to_interleaved(output_planes(output, 1, width, height)?, |_, value| {
+            (value * 255.0).clamp(0.0, 255.0) as u8
+        });
+        if let Some(original) = &params.original {
+            let mask = image::GrayImage::from_raw(width as u32, height as u32, alpha)
+                .ok_or_else(wrong_size)?;
+            return Ok(apply_mask(original, &mask));
         }
 
+        let samples = alpha
+            .par_iter()
+            .flat_map_iter(|&alpha| [0, 0, 0, alpha])
+            .collect();
+        let img_buffer = image::RgbaImage::from_raw(width as u32, height as u32, samples)
+            .ok_or_else(wrong_size)?;
+
         let mut result = DynamicImage::ImageRgba8(img_buffer);
 
         // Resize back to original dimensions if they exist
@@ -301,25 +294,776 @@ impl ImageModel for BackgroundRemovalModel<f32> {
 
     fn process(
         session: &Session,
-        input: &TensorInput<Self::InputType>,
+        input: Self::Input,
     ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
-        let input_array = input.clone().into_dyn();
-        let input_value = Value::from_array(input_array)?;
-        let inputs = inputs![input_value]?;
+        let inputs = inputs![Value::from_array(input)?]?;
 
         let outputs = session.run(inputs)?;
         let output = outputs
             .get("output")
             .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
 
-        let output_tensor = output.try_extract_tensor::<Self::InputType>()?;
-        let output_shape = output_tensor.shape();
+        // A single channel mask at the input size
+        output_tensor(output)
+    }
+}
+
+pub struct DenoiseModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for DenoiseModel<f32> {
+    type Params = DenoiseParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = TensorInput<Self::InputType>;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?.into_rgb32f();
+
+        // Save original dimensions to crop the padding away in postprocessing
+        params.original_width = Some(image.width());
+        params.original_height = Some(image.height());
+
+        let tensor = padded_image_to_tensor(&image, params.pad_multiple);
+        if params.strength < 1.0 {
+            params.input = Some(image);
+        }
+
+        Ok(tensor)
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, _, height, width) = output.dim();
+
+        blended_tensor_to_image(
+            output,
+            params.input.as_ref(),
+            params.strength,
+            params.original_width.unwrap_or(width as u32),
+            params.original_height.unwrap_or(height as u32),
+        )
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let inputs = inputs![Value::from_array(input)?]?;
+
+        let outputs = session.run(inputs)?;
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        output_tensor(&output)
+    }
+}
+
+/// Lab normalization used by colorization models trained on `(L - 50) / 100` inputs and
+/// `ab / 110` outputs.
+const L_CENTER: f32 = 50.0;
+const L_NORM: f32 = 100.0;
+const AB_NORM: f32 = 110.0;
+
+pub struct ColorizationModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for ColorizationModel<f32> {
+    type Params = ColorizationParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = TensorInput<Self::InputType>;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?.into_rgb32f();
+
+        // Keep the full-resolution luminance, only the chroma is predicted at model resolution
+        params.original_width = Some(image.width());
+        params.original_height = Some(image.height());
+        params.luminance = Some(
+            image
+                .par_chunks(3)
+                .map(|pixel| rgb_to_lab([pixel[0], pixel[1], pixel[2]])[0])
+                .collect(),
+        );
+
+        let resized = image::imageops::resize(
+            &image,
+            params.model_width as u32,
+            params.model_height as u32,
+            image::imageops::FilterType::Triangle,
+        );
+
+        let width = params.model_width;
+        Ok(planar_from_rows(
+            1,
+            params.model_height,
+            width,
+            |_, y, row| {
+                let pixels = resized.as_raw()[y * width * 3..(y + 1) * width * 3].chunks_exact(3);
+                for (value, pixel) in row.iter_mut().zip(pixels) {
+                    let l = rgb_to_lab([pixel[0], pixel[1], pixel[2]])[0];
+                    *value = (l - L_CENTER) / L_NORM;
+                }
+            },
+        ))
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, _, height, width) = output.dim();
+        let (Some(original_width), Some(original_height), Some(luminance)) = (
+            params.original_width,
+            params.original_height,
+            params.luminance.as_ref(),
+        ) else {
+            return Err(ImageProcessingError::Processing(
+                "Missing luminance for colorization".to_string(),
+            ));
+        };
+
+        // Upsample the predicted ab channels to the original resolution
+        let ab = planar_to_interleaved(output_planes(output, 2, width, height)?, |_, value| {
+            value * AB_NORM
+        });
+        let samples = ab
+            .par_chunks(2)
+            .flat_map_iter(|ab| [ab[0], ab[1], 0.0])
+            .collect();
+        let chroma = image::Rgb32FImage::from_raw(width as u32, height as u32, samples)
+            .ok_or_else(|| {
+                ImageProcessingError::Processing("Expected two chroma channels".to_string())
+            })?;
+        let chroma = image::imageops::resize(
+            &chroma,
+            original_width,
+            original_height,
+            image::imageops::FilterType::Triangle,
+        );
+
+        let mut img_buffer = image::Rgb32FImage::new(original_width, original_height);
+        img_buffer
+            .par_chunks_mut(3)
+            .zip(luminance.par_iter())
+            .zip(chroma.par_chunks(3))
+            .for_each(|((pixel, &l), ab)| {
+                pixel.copy_from_slice(&lab_to_rgb([
+                    l,
+                    ab[0] * params.saturation,
+                    ab[1] * params.saturation,
+                ]));
+            });
+
+        Ok(DynamicImage::ImageRgb32F(img_buffer))
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let inputs = inputs![Value::from_array(input)?]?;
+
+        let outputs = session.run(inputs)?;
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        // The model predicts the two chroma channels (a, b)
+        output_tensor(&output)
+    }
+}
+
+pub struct InpaintingModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for InpaintingModel<f32> {
+    type Params = InpaintingParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = InpaintingInput;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
+        let mask = match &params.mask {
+            Some(MaskSource::Path(path)) => {
+                validate(path, 1)?;
+                open_image(path)?
+            }
+            Some(MaskSource::Bytes(bytes)) => decode_bytes(bytes)?,
+            None => {
+                return Err(ImageProcessingError::Processing(
+                    "No mask provided for inpainting".to_string(),
+                ))
+            }
+        };
+
+        // Masks drawn at a different resolution are stretched over the image
+        let mask = mask.into_luma8();
+        let mask = if mask.dimensions() == image.dimensions() {
+            mask
+        } else {
+            image::imageops::resize(
+                &mask,
+                image.width(),
+                image.height(),
+                image::imageops::FilterType::Triangle,
+            )
+        };
+
+        let (model_width, model_height) = (params.model_width as u32, params.model_height as u32);
+        let resized_image = image
+            .resize_exact(
+                model_width,
+                model_height,
+                image::imageops::FilterType::Triangle,
+            )
+            .into_rgb8();
+        let resized_mask = image::imageops::resize(
+            &mask,
+            model_width,
+            model_height,
+            image::imageops::FilterType::Nearest,
+        );
+
+        let image_tensor = normalized_tensor(&resized_image, &Normalization::unit(255.0));
+        let mask_tensor = interleaved_to_planar(
+            resized_mask.as_raw(),
+            1,
+            params.model_height,
+            params.model_width,
+            |_, sample| if sample > 127 { 1.0 } else { 0.0 },
+        );
+
+        params.original = Some(image);
+        params.original_mask = Some(mask);
+
+        Ok(InpaintingInput {
+            image: image_tensor,
+            mask: mask_tensor,
+        })
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (Some(original), Some(mask)) = (&params.original, &params.original_mask) else {
+            return Err(ImageProcessingError::Processing(
+                "Missing original image for inpainting".to_string(),
+            ));
+        };
+
+        // LaMa-style models output pixel values in 0..=255
+        let inpainted = denormalized_rgb8(output, &Normalization::unit(1.0))?;
+        let inpainted = image::imageops::resize(
+            &inpainted,
+            original.width(),
+            original.height(),
+            image::imageops::FilterType::Lanczos3,
+        );
+
+        // Only replace the masked area so the rest of the image keeps its full resolution, alpha
+        // and bit depth
+        let mut result = original.to_rgba32f();
+        result
+            .par_chunks_mut(4)
+            .zip(mask.par_iter())
+            .zip(inpainted.par_chunks(3))
+            .for_each(|((pixel, &mask), fill)| {
+                let alpha = mask as f32 / 255.0;
+                for c in 0..3 {
+                    pixel[c] = pixel[c] * (1.0 - alpha) + fill[c] as f32 / 255.0 * alpha;
+                }
+            });
+
+        Ok(match_color_type(
+            DynamicImage::ImageRgba32F(result),
+            original,
+        ))
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let inputs = inputs![
+            Value::from_array(input.image)?,
+            Value::from_array(input.mask)?
+        ]?;
+
+        let outputs = session.run(inputs)?;
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        output_tensor(&output)
+    }
+}
+
+/// Default quality assumed for models that require a quality factor when none was given.
+const DEFAULT_JPEG_QUALITY: f32 = 50.0;
+
+/// Deblurring / JPEG artifact removal (NAFNet-style single input, or FBCNN-style with an
+/// additional quality factor input).
+pub struct ArtifactRemovalModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for ArtifactRemovalModel<f32> {
+    type Params = ArtifactRemovalParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = ArtifactRemovalInput;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?.into_rgb32f();
+
+        params.original_width = Some(image.width());
+        params.original_height = Some(image.height());
+
+        let tensor = padded_image_to_tensor(&image, params.pad_multiple);
+        if params.strength < 1.0 {
+            params.input = Some(image);
+        }
+
+        // FBCNN expects the quality factor as `1 - quality / 100`
+        let quality = params.quality.map(|quality| {
+            let factor = 1.0 - quality.clamp(1.0, 100.0) / 100.0;
+            ndarray::Array2::from_elem((1, 1), factor)
+        });
+
+        Ok(ArtifactRemovalInput {
+            image: tensor,
+            quality,
+        })
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, _, height, width) = output.dim();
+
+        blended_tensor_to_image(
+            output,
+            params.input.as_ref(),
+            params.strength,
+            params.original_width.unwrap_or(width as u32),
+            params.original_height.unwrap_or(height as u32),
+        )
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let image_value = Value::from_array(input.image)?;
+
+        let outputs = if session.inputs.len() > 1 {
+            let quality = input.quality.unwrap_or_else(|| {
+                ndarray::Array2::from_elem((1, 1), 1.0 - DEFAULT_JPEG_QUALITY / 100.0)
+            });
+            let quality_value = Value::from_array(quality)?;
+            session.run(inputs![image_value, quality_value]?)?
+        } else {
+            session.run(inputs![image_value]?)?
+        };
+
+        // Models with several outputs (e.g. FBCNN also predicts the quality) list the image first
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        output_tensor(&output)
+    }
+}
+
+pub struct EnhanceModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for EnhanceModel<f32> {
+    type Params = EnhanceParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = TensorInput<Self::InputType>;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?.into_rgb32f();
+
+        params.original_width = Some(image.width());
+        params.original_height = Some(image.height());
+
+        // Zero-DCE++ downsamples internally by 4, so keep the size divisible by it
+        let tensor = padded_image_to_tensor(&image, 4);
+        params.input = Some(image);
+
+        Ok(tensor)
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, channels, height, width) = output.dim();
+        let width = params.original_width.unwrap_or(width as u32);
+        let height = params.original_height.unwrap_or(height as u32);
+
+        if channels == 3 {
+            return blended_tensor_to_image(
+                output,
+                params.input.as_ref(),
+                params.intensity,
+                width,
+                height,
+            );
+        }
+
+        // Curve parameter maps (3 per iteration): apply LE(x) = x + a * (x^2 - x) repeatedly,
+        // scaling each curve by the intensity
+        let input = params.input.as_ref().ok_or_else(|| {
+            ImageProcessingError::Processing("Missing input for curve estimation".to_string())
+        })?;
+        let iterations = channels / 3;
+        let curves = output_planes(output, iterations * 3, width as usize, height as usize)?;
+        let mut img_buffer = input.clone();
+
+        img_buffer
+            .par_chunks_mut(width as usize * 3)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                    for (c, v) in pixel.iter_mut().enumerate() {
+                        for i in 0..iterations {
+                            let alpha = curves[[0, i * 3 + c, y, x]] * params.intensity;
+                            *v = (*v + alpha * (*v * *v - *v)).clamp(0.0, 1.0);
+                        }
+                    }
+                }
+            });
+
+        Ok(DynamicImage::ImageRgb32F(img_buffer))
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let input_value = Value::from_array(input)?;
+        let outputs = session.run(inputs![input_value]?)?;
 
-        // Create a new tensor with the alpha channel values
-        let alpha_values = output_tensor.as_slice().unwrap().to_vec();
+        // Zero-DCE exports return intermediate images next to the curve maps; prefer the curve
+        // maps (most channels) and otherwise the first image
+        let mut best: Option<(&str, usize)> = None;
+        for (name, value) in outputs.iter() {
+            let tensor = value.try_extract_tensor::<Self::OutputType>()?;
+            let shape = tensor.shape();
+            if shape.len() != 4 || shape[1] % 3 != 0 {
+                continue;
+            }
+            if best.is_some_and(|(_, channels)| channels >= shape[1]) {
+                continue;
+            }
+            best = Some((name, shape[1]));
+        }
+
+        let (name, _) = best
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+        output_tensor(&outputs[name])
+    }
+}
+
+/// Per-pixel ImageNet normalization used by most segmentation backbones.
+const IMAGENET_NORMALIZATION: Normalization = Normalization {
+    scale: 255.0,
+    mean: [0.485, 0.456, 0.406],
+    std: [0.229, 0.224, 0.225],
+};
+
+/// Semantic segmentation (SegFormer/DeepLab-style). The output image is a `Luma8` label map at
+/// the model's output resolution, holding the most likely class index of every pixel.
+pub struct SegmentationModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for SegmentationModel<f32> {
+    type Params = SegmentationParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = TensorInput<Self::InputType>;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
+        let resized = image
+            .resize_exact(
+                params.model_width as u32,
+                params.model_height as u32,
+                image::imageops::FilterType::Triangle,
+            )
+            .into_rgb8();
+        params.original = Some(image);
+
+        Ok(normalized_tensor(&resized, &IMAGENET_NORMALIZATION))
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        _params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, classes, height, width) = output.dim();
+        if classes > u8::MAX as usize + 1 {
+            return Err(ImageProcessingError::Processing(format!(
+                "Segmentation models with {} classes are not supported",
+                classes
+            )));
+        }
+
+        let logits = output.as_standard_layout();
+        let logits = logits.as_slice().expect("standard layout is contiguous");
+        let plane = width * height;
+        let labels = (0..plane)
+            .into_par_iter()
+            .map(|i| {
+                let best = (0..classes)
+                    .max_by(|&a, &b| logits[a * plane + i].total_cmp(&logits[b * plane + i]))
+                    .unwrap_or(0);
+                best as u8
+            })
+            .collect();
+
+        image::GrayImage::from_raw(width as u32, height as u32, labels)
+            .map(DynamicImage::ImageLuma8)
+            .ok_or_else(|| {
+                ImageProcessingError::Processing("Labels have the wrong size".to_string())
+            })
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let input_value = Value::from_array(input)?;
+        let outputs = session.run(inputs![input_value]?)?;
+
+        // Logits of shape (1, classes, height, width), often at a fraction of the input size
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        output_tensor(&output)
+    }
+}
+
+/// SAM's pixel normalization, in 0-255 units.
+const SAM_NORMALIZATION: Normalization = Normalization {
+    scale: 1.0,
+    mean: [123.675, 116.28, 103.53],
+    std: [58.395, 57.12, 57.375],
+};
+
+/// Image encoder of a SAM-style promptable segmentation model. Its output is the image
+/// embedding fed to [`PromptDecoderModel`]; it is run with `ModelProcessor::infer`.
+pub struct PromptEncoderModel<T = f32>(PhantomData<T>);
+
+impl ImageModel for PromptEncoderModel<f32> {
+    type Params = PromptEncoderParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = TensorInput<Self::InputType>;
+
+    fn preprocess(
+        image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        let image = open_image(image_path)?;
+        let size = params.input_size;
+
+        // Resize the longest side to the input size and pad the rest with zeros (after
+        // normalization), as SAM does
+        let scale = size as f32 / image.width().max(image.height()) as f32;
+        let width = ((image.width() as f32 * scale).round() as u32).clamp(1, size);
+        let height = ((image.height() as f32 * scale).round() as u32).clamp(1, size);
+        let resized = image
+            .resize_exact(width, height, image::imageops::FilterType::Triangle)
+            .into_rgb8();
+
+        let (width, height) = (width as usize, height as usize);
+        Ok(planar_from_rows(
+            3,
+            size as usize,
+            size as usize,
+            |c, y, row| {
+                if y >= height {
+                    return;
+                }
+                let pixels = &resized.as_raw()[y * width * 3..(y + 1) * width * 3];
+                for (value, sample) in row.iter_mut().zip(pixels.iter().skip(c).step_by(3)) {
+                    *value = SAM_NORMALIZATION.normalize(c, *sample as f32);
+                }
+            },
+        ))
+    }
+
+    fn postprocess(
+        _output: &TensorOutput<Self::OutputType>,
+        _params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        Err(ImageProcessingError::Processing(
+            "The prompt encoder produces embeddings, not images".to_string(),
+        ))
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let input_value = Value::from_array(input)?;
+        let outputs = session.run(inputs![input_value]?)?;
+
+        let output = outputs
+            .values()
+            .next()
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        output_tensor(&output)
+    }
+}
+
+/// Mask decoder of a SAM-style model. Takes the cached image embedding and the prompts from
+/// the params (the image itself is not read) and produces a `Luma8` mask at the original size.
+pub struct PromptDecoderModel<T = f32>(PhantomData<T>);
+
+impl PromptDecoderModel<f32> {
+    /// Builds the decoder input from the cached embedding and prompts in `params`.
+    pub fn input(params: &PromptDecoderParams) -> Result<PromptDecoderInput, ImageProcessingError> {
+        let embedding = params.embedding.clone().ok_or_else(|| {
+            ImageProcessingError::Processing("Missing image embedding".to_string())
+        })?;
+        if params.points.is_empty() && params.prompt_box.is_none() {
+            return Err(ImageProcessingError::Processing(
+                "At least one point or a box is required".to_string(),
+            ));
+        }
+
+        // Labels: 1 foreground, 0 background, 2/3 box corners, -1 padding
+        let mut prompts: Vec<(f32, f32, f32)> = params
+            .points
+            .iter()
+            .map(|p| (p.x, p.y, if p.foreground { 1.0 } else { 0.0 }))
+            .collect();
+        match params.prompt_box {
+            Some(b) => {
+                prompts.push((b.x0.min(b.x1), b.y0.min(b.y1), 2.0));
+                prompts.push((b.x0.max(b.x1), b.y0.max(b.y1), 3.0));
+            }
+            None => prompts.push((0.0, 0.0, -1.0)),
+        }
+
+        let point_coords = ndarray::Array3::from_shape_fn((1, prompts.len(), 2), |(_, i, axis)| {
+            let (x, y, _) = prompts[i];
+            if axis == 0 {
+                x * params.scale
+            } else {
+                y * params.scale
+            }
+        });
+        let point_labels =
+            ndarray::Array2::from_shape_fn((1, prompts.len()), |(_, i)| prompts[i].2);
+        let original_size =
+            ndarray::arr1(&[params.original_height as f32, params.original_width as f32]);
+
+        Ok(PromptDecoderInput {
+            embedding,
+            point_coords,
+            point_labels,
+            original_size,
+        })
+    }
+}
+
+impl ImageModel for PromptDecoderModel<f32> {
+    type Params = PromptDecoderParams;
+    type InputType = f32;
+    type OutputType = f32;
+    type Input = PromptDecoderInput;
+
+    fn preprocess(
+        _image_path: &str,
+        params: &mut Self::Params,
+    ) -> Result<Self::Input, ImageProcessingError> {
+        Self::input(params)
+    }
+
+    fn postprocess(
+        output: &TensorOutput<Self::OutputType>,
+        _params: &Self::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let (_, _, height, width) = output.dim();
+
+        // The decoder returns mask logits; positive values are inside the object
+        let mask = planar_to_interleaved(output_planes(output, 1, width, height)?, |_, logit| {
+            if logit > 0.0 {
+                255
+            } else {
+                0
+            }
+        });
+
+        image::GrayImage::from_raw(width as u32, height as u32, mask)
+            .map(DynamicImage::ImageLuma8)
+            .ok_or_else(|| ImageProcessingError::Processing("Empty mask".to_string()))
+    }
+
+    fn process(
+        session: &Session,
+        input: Self::Input,
+    ) -> Result<TensorOutput<Self::OutputType>, ImageProcessingError> {
+        let mask_input = ndarray::Array4::<f32>::zeros((1, 1, 256, 256));
+        let has_mask_input = ndarray::arr1(&[0.0f32]);
+
+        let outputs = session.run(inputs![
+            // The cached embedding is shared with later prompts, so it is the one input copied
+            "image_embeddings" => Value::from_array(input.embedding.as_ref().clone())?,
+            "point_coords" => Value::from_array(input.point_coords)?,
+            "point_labels" => Value::from_array(input.point_labels)?,
+            "mask_input" => Value::from_array(mask_input)?,
+            "has_mask_input" => Value::from_array(has_mask_input)?,
+            "orig_im_size" => Value::from_array(input.original_size)?,
+        ]?)?;
+
+        let output = outputs
+            .get("masks")
+            .ok_or_else(|| ImageProcessingError::Processing("No output from model".to_string()))?;
+
+        let output_tensor = output.try_extract_tensor::<Self::OutputType>()?;
+        let output_shape = output_tensor.shape();
+        let (height, width) = (
+            output_shape[output_shape.len() - 2],
+            output_shape[output_shape.len() - 1],
+        );
 
-        // The output should match the input dimensions but with single channel
-        ndarray::Array4::from_shape_vec((1, 1, output_shape[2], output_shape[3]), alpha_values)
+        // Keep only the first (best) mask when several are returned
+        let mask: Vec<f32> = output_tensor.iter().copied().take(height * width).collect();
+        ndarray::Array4::from_shape_vec((1, 1, height, width), mask)
             .map_err(|e| ImageProcessingError::Processing(e.to_string()))
     }
 }
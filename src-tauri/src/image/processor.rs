@@ -1,9 +1,51 @@
 use image::DynamicImage;
 use rayon::prelude::*;
+use serde::Serialize;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
+use super::session::{session_config, SessionConfig};
+use super::types::TensorOutput;
+use super::validate::validate;
 use super::{ImageModel, ImageProcessingError};
 
+/// Share of the total work attributed to each stage, used to turn stage transitions into an
+/// overall fraction.
+const PREPROCESS_WEIGHT: f32 = 0.1;
+const INFERENCE_WEIGHT: f32 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStage {
+    Preprocess,
+    Inference,
+    Postprocess,
+}
+
+/// Progress of a job: the current stage and the overall fraction complete, in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub stage: ProcessingStage,
+    pub fraction: f32,
+}
+
+pub type ProgressFn<'a> = dyn Fn(Progress) + Sync + 'a;
+
+/// Wall-clock time spent in each stage of one run.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct StageTimings {
+    pub preprocess_ms: f64,
+    pub inference_ms: f64,
+    pub postprocess_ms: f64,
+}
+
+impl StageTimings {
+    pub fn total_ms(&self) -> f64 {
+        self.preprocess_ms + self.inference_ms + self.postprocess_ms
+    }
+}
+
 pub struct ModelProcessor<M: ImageModel + Send + Sync> {
     session: ort::session::Session,
     _phantom: PhantomData<M>,
@@ -11,35 +53,148 @@ pub struct ModelProcessor<M: ImageModel + Send + Sync> {
 
 impl<M: ImageModel + Send + Sync> ModelProcessor<M> {
     pub fn new(model_path: &str) -> Result<Self, ImageProcessingError> {
-        let session = M::load_session(model_path)?;
+        Self::with_config(model_path, &session_config())
+    }
+
+    /// Loads the model with other session settings than the configured ones, e.g. to compare
+    /// thread counts.
+    pub fn with_config(
+        model_path: &str,
+        config: &SessionConfig,
+    ) -> Result<Self, ImageProcessingError> {
+        let session = M::load_session(model_path, config)?;
         Ok(Self {
             session,
             _phantom: PhantomData,
         })
     }
 
+    /// Runs preprocessing and inference only, for models whose raw output is consumed elsewhere
+    /// (e.g. image embeddings).
+    pub fn infer(
+        &self,
+        image_path: &str,
+        params: &M::Params,
+    ) -> Result<TensorOutput<M::OutputType>, ImageProcessingError> {
+        validate(image_path, M::OUTPUT_SCALE)?;
+        let mut params = params.clone();
+        let input = M::preprocess(image_path, &mut params)?;
+        M::process(&self.session, input)
+    }
+
+    /// Runs inference and postprocessing on an input built without reading an image, e.g. from
+    /// a cached embedding.
+    pub fn process_input(
+        &self,
+        input: M::Input,
+        params: &M::Params,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        let output = M::process(&self.session, input)?;
+        M::postprocess(&output, params)
+    }
+
     pub fn process_single(
         &self,
         image_path: &str,
         params: &M::Params,
     ) -> Result<DynamicImage, ImageProcessingError> {
+        self.process_single_with_progress(image_path, params, &|_| {})
+    }
+
+    pub fn process_single_with_progress(
+        &self,
+        image_path: &str,
+        params: &M::Params,
+        progress: &ProgressFn,
+    ) -> Result<DynamicImage, ImageProcessingError> {
+        self.process_single_with_params(image_path, params, progress)
+            .map(|(image, _)| image)
+    }
+
+    /// Processes one image like [`Self::process_single_with_progress`], also returning the
+    /// params as preprocessing filled them in, e.g. with the decoded input.
+    pub fn process_single_with_params(
+        &self,
+        image_path: &str,
+        params: &M::Params,
+        progress: &ProgressFn,
+    ) -> Result<(DynamicImage, M::Params), ImageProcessingError> {
+        let report = |stage, fraction| progress(Progress { stage, fraction });
+
+        let mut params = params.clone();
+        report(ProcessingStage::Preprocess, 0.0);
+        validate(image_path, M::OUTPUT_SCALE)?;
+        let input = M::preprocess(image_path, &mut params)?;
+
+        report(ProcessingStage::Inference, PREPROCESS_WEIGHT);
+        let output = M::process(&self.session, input)?;
+
+        report(
+            ProcessingStage::Postprocess,
+            PREPROCESS_WEIGHT + INFERENCE_WEIGHT,
+        );
+        let image = M::postprocess(&output, &params)?;
+
+        report(ProcessingStage::Postprocess, 1.0);
+        Ok((image, params))
+    }
+
+    /// Processes one image like [`Self::process_single`], measuring how long each stage takes.
+    pub fn process_single_timed(
+        &self,
+        image_path: &str,
+        params: &M::Params,
+    ) -> Result<(DynamicImage, StageTimings), ImageProcessingError> {
+        let elapsed_ms = |start: Instant| start.elapsed().as_secs_f64() * 1000.0;
         let mut params = params.clone();
+
+        let start = Instant::now();
         let input = M::preprocess(image_path, &mut params)?;
-        let output = M::process(&self.session, &input)?;
-        M::postprocess(&output, &params)
+        let preprocess_ms = elapsed_ms(start);
+
+        let start = Instant::now();
+        let output = M::process(&self.session, input)?;
+        let inference_ms = elapsed_ms(start);
+
+        let start = Instant::now();
+        let image = M::postprocess(&output, &params)?;
+        let postprocess_ms = elapsed_ms(start);
+
+        Ok((
+            image,
+            StageTimings {
+                preprocess_ms,
+                inference_ms,
+                postprocess_ms,
+            },
+        ))
     }
 
-    pub fn process_batch<I>(
+    /// Processes a batch, reporting the fraction of images completed so far.
+    pub fn process_batch_with_progress<I>(
         &self,
         image_paths: I,
         params: &M::Params,
+        progress: &ProgressFn,
     ) -> Result<Vec<DynamicImage>, ImageProcessingError>
     where
         I: IntoParallelIterator<Item = String>,
+        I::Iter: IndexedParallelIterator,
     {
+        let image_paths = image_paths.into_par_iter();
+        let total = image_paths.len();
+        let completed = AtomicUsize::new(0);
+
         image_paths
-            .into_par_iter()
-            .map(|path| self.process_single(&path, params))
+            .map(|path| {
+                let image = self.process_single(&path, params)?;
+                let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                progress(Progress {
+                    stage: ProcessingStage::Postprocess,
+                    fraction: done as f32 / total as f32,
+                });
+                Ok(image)
+            })
             .collect()
     }
 }
@@ -0,0 +1,88 @@
+//! How ONNX sessions are built. The defaults suit most machines; `benchmark_models` measures
+//! which thread count is fastest on the current one.
+
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::error::ImageProcessingError;
+use crate::utils::{load_json_config, save_json_config};
+
+const SESSION_CONFIG_FILE: &str = "session.json";
+
+static SESSION_CONFIG: OnceLock<Mutex<SessionConfig>> = OnceLock::new();
+
+/// Graph optimizations ONNX Runtime applies when loading a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationLevel {
+    Disable,
+    Level1,
+    Level2,
+    Level3,
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Level1 => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Level2 => GraphOptimizationLevel::Level2,
+            OptimizationLevel::Level3 => GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Threads a single session uses for one inference.
+    pub intra_threads: usize,
+    pub optimization_level: OptimizationLevel,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            intra_threads: 4,
+            optimization_level: OptimizationLevel::Level3,
+        }
+    }
+}
+
+fn config_cell() -> &'static Mutex<SessionConfig> {
+    SESSION_CONFIG
+        .get_or_init(|| Mutex::new(load_json_config(SESSION_CONFIG_FILE).unwrap_or_default()))
+}
+
+pub fn session_config() -> SessionConfig {
+    *config_cell().lock().unwrap()
+}
+
+/// Changes how sessions are built. Models that are already loaded keep their settings until
+/// they are reloaded.
+pub fn set_session_config(config: SessionConfig) -> Result<(), CommandError> {
+    if config.intra_threads == 0 {
+        return Err(CommandError::InvalidArgument(
+            "Sessions need at least one thread".to_string(),
+        ));
+    }
+    *config_cell().lock().unwrap() = config;
+    info!("Session config set to {:?}", config);
+
+    save_json_config(SESSION_CONFIG_FILE, &config)
+}
+
+/// Loads the model at `model_path` into a session built with `config`.
+pub fn build_session(
+    model_path: &str,
+    config: &SessionConfig,
+) -> Result<Session, ImageProcessingError> {
+    Session::builder()?
+        .with_optimization_level(config.optimization_level.into())?
+        .with_intra_threads(config.intra_threads)?
+        .commit_from_file(model_path)
+        .map_err(|e| ImageProcessingError::Ort(e.to_string()))
+}
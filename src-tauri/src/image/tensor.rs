@@ -1,39 +1,524 @@
-use image::DynamicImage;
+use image::{DynamicImage, ImageBuffer, Rgb};
+use ndarray::{s, ArrayView4};
+use rayon::prelude::*;
 
 use crate::image::error::ImageProcessingError;
 use crate::image::types::{NumericType, TensorInput, TensorOutput};
 
+/// Per-channel mapping between pixel samples and model inputs: a sample becomes
+/// `(sample / scale - mean) / std`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub scale: f32,
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Normalization {
+    /// Only divides by `scale`, e.g. 255 to map 8-bit samples to `[0, 1]`.
+    pub const fn unit(scale: f32) -> Self {
+        Self::uniform(scale, 0.0, 1.0)
+    }
+
+    /// The same `mean` and `std` for every channel.
+    pub const fn uniform(scale: f32, mean: f32, std: f32) -> Self {
+        Self {
+            scale,
+            mean: [mean; 3],
+            std: [std; 3],
+        }
+    }
+
+    pub fn normalize(&self, channel: usize, sample: f32) -> f32 {
+        (sample / self.scale - self.mean[channel]) / self.std[channel]
+    }
+
+    pub fn denormalize(&self, channel: usize, value: f32) -> f32 {
+        (value * self.std[channel] + self.mean[channel]) * self.scale
+    }
+}
+
+/// Fills a `[1, channels, height, width]` tensor in parallel, one row of a channel plane at a
+/// time, from `fill_row(channel, y, row)`.
+pub fn planar_from_rows<T: Copy + Default + Send>(
+    channels: usize,
+    height: usize,
+    width: usize,
+    fill_row: impl Fn(usize, usize, &mut [T]) + Sync,
+) -> TensorInput<T> {
+    let mut tensor = TensorInput::<T>::default((1, channels, height, width));
+    if width > 0 {
+        tensor
+            .as_slice_mut()
+            .expect("new tensors are contiguous")
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(row, values)| fill_row(row / height, row % height, values));
+    }
+    tensor
+}
+
+/// Splits interleaved samples (as stored by `image` buffers) into the channel planes of a
+/// `[1, channels, height, width]` tensor, converting each with `value(channel, sample)`.
+pub fn interleaved_to_planar<S: Copy + Sync, T: Copy + Default + Send>(
+    samples: &[S],
+    channels: usize,
+    height: usize,
+    width: usize,
+    value: impl Fn(usize, S) -> T + Sync,
+) -> TensorInput<T> {
+    planar_from_rows(channels, height, width, |c, y, row| {
+        let pixels = &samples[y * width * channels..(y + 1) * width * channels];
+        for (output, sample) in row.iter_mut().zip(pixels.iter().skip(c).step_by(channels)) {
+            *output = value(c, *sample);
+        }
+    })
+}
+
+/// Interleaves the channel planes of a `[1, C, H, W]` tensor into `image` buffer order,
+/// converting each value with `value(channel, value)`.
+pub fn planar_to_interleaved<T: Copy + Sync, P: Copy + Default + Send>(
+    tensor: ArrayView4<T>,
+    value: impl Fn(usize, T) -> P + Sync,
+) -> Vec<P> {
+    let (_, channels, height, width) = tensor.dim();
+    let tensor = tensor.as_standard_layout();
+    let planes = tensor.as_slice().expect("standard layout is contiguous");
+
+    let mut samples = vec![P::default(); channels * height * width];
+    if width > 0 {
+        samples
+            .par_chunks_mut(width * channels)
+            .enumerate()
+            .for_each(|(y, pixels)| {
+                for c in 0..channels {
+                    let plane_row = &planes[(c * height + y) * width..(c * height + y + 1) * width];
+                    for (output, value_in) in
+                        pixels.iter_mut().skip(c).step_by(channels).zip(plane_row)
+                    {
+                        *output = value(c, *value_in);
+                    }
+                }
+            });
+    }
+    samples
+}
+
+/// The first `channels` planes of the first image in a model output, cropped to `width`x`height`
+/// from the top left. Fails instead of panicking when the output is smaller than that.
+pub fn output_planes<T>(
+    tensor: &TensorOutput<T>,
+    channels: usize,
+    width: usize,
+    height: usize,
+) -> Result<ArrayView4<'_, T>, ImageProcessingError> {
+    let (batch, output_channels, output_height, output_width) = tensor.dim();
+    if batch == 0 {
+        return Err(ImageProcessingError::Processing(
+            "Output is empty".to_string(),
+        ));
+    }
+    if output_channels < channels {
+        return Err(ImageProcessingError::Processing(format!(
+            "Output has {} channels, expected at least {}",
+            output_channels, channels
+        )));
+    }
+    if output_width < width || output_height < height {
+        return Err(ImageProcessingError::Processing(format!(
+            "Output is {}x{}, expected at least {}x{}",
+            output_width, output_height, width, height
+        )));
+    }
+    Ok(tensor.slice(s![..1, ..channels, ..height, ..width]))
+}
+
+fn unfilled_image() -> ImageProcessingError {
+    ImageProcessingError::Processing("Output does not fill the image".to_string())
+}
+
+/// Converts an RGB image to a normalized `f32` tensor.
+pub fn normalized_tensor<S>(
+    image: &ImageBuffer<Rgb<S>, Vec<S>>,
+    normalization: &Normalization,
+) -> TensorInput<f32>
+where
+    Rgb<S>: image::Pixel<Subpixel = S>,
+    S: Copy + Into<f32> + Sync,
+{
+    let (width, height) = image.dimensions();
+    interleaved_to_planar(
+        image.as_raw(),
+        3,
+        height as usize,
+        width as usize,
+        |c, sample| normalization.normalize(c, sample.into()),
+    )
+}
+
+/// Converts the first three channels of a normalized tensor back to an 8-bit RGB image,
+/// clamping out-of-range values.
+pub fn denormalized_rgb8(
+    tensor: &TensorOutput<f32>,
+    normalization: &Normalization,
+) -> Result<image::RgbImage, ImageProcessingError> {
+    let (_, _, height, width) = tensor.dim();
+    let planes = output_planes(tensor, 3, width, height)?;
+    let samples = planar_to_interleaved(planes, |c, value| {
+        normalization.denormalize(c, value).clamp(0.0, 255.0) as u8
+    });
+    image::RgbImage::from_raw(width as u32, height as u32, samples).ok_or_else(unfilled_image)
+}
+
+/// Converts an image to a `[0, 1]` tensor, going through `f32` so 16-bit and HDR inputs keep
+/// their precision.
 pub fn image_to_tensor<T: NumericType>(
     image: &DynamicImage,
 ) -> Result<TensorInput<T>, ImageProcessingError> {
-    let rgb_image = image.to_rgb8();
+    let rgb_image = image.to_rgb32f();
     let (width, height) = rgb_image.dimensions();
 
-    let tensor =
-        ndarray::Array::from_shape_fn((1, 3, height as usize, width as usize), |(_, c, y, x)| {
-            let pixel = rgb_image.get_pixel(x as u32, y as u32);
-            T::from_f32(pixel[c] as f32 / 255.0)
-        });
-
-    Ok(tensor)
+    Ok(interleaved_to_planar(
+        rgb_image.as_raw(),
+        3,
+        height as usize,
+        width as usize,
+        |_, sample| T::from_f32(sample),
+    ))
 }
 
+/// Converts a `[0, 1]` tensor to an `f32` image; quantization happens only when saving.
 pub fn tensor_to_image<T: NumericType>(
     tensor: &TensorOutput<T>,
 ) -> Result<DynamicImage, ImageProcessingError> {
     let (_, _, h, w) = tensor.dim();
-    let mut img_buffer = image::RgbImage::new(w as u32, h as u32);
+    let planes = output_planes(tensor, 3, w, h)?;
+    let samples = planar_to_interleaved(planes, |_, value| value.to_f32().clamp(0.0, 1.0));
+
+    image::Rgb32FImage::from_raw(w as u32, h as u32, samples)
+        .map(DynamicImage::ImageRgb32F)
+        .ok_or_else(unfilled_image)
+}
+
+/// Converts an image to a `[0, 1]` tensor padded to a multiple of `multiple` by repeating the
+/// edge pixels, for models that work on fixed-size windows.
+pub fn padded_image_to_tensor(image: &image::Rgb32FImage, multiple: u32) -> TensorInput<f32> {
+    let (width, height) = image.dimensions();
+    let multiple = multiple.max(1);
+    let padded_width = width.div_ceil(multiple) * multiple;
+    let padded_height = height.div_ceil(multiple) * multiple;
+
+    let (width, height) = (width as usize, height as usize);
+    let samples = image.as_raw();
+    planar_from_rows(
+        3,
+        padded_height as usize,
+        padded_width as usize,
+        |c, y, row| {
+            let pixels = &samples[y.min(height - 1) * width * 3..][..width * 3];
+            let edge = pixels[(width - 1) * 3 + c];
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = if x < width { pixels[x * 3 + c] } else { edge };
+            }
+        },
+    )
+}
+
+/// Crops a `[0, 1]` output tensor to `width`x`height`, blending it with `input` (an image of
+/// that size) by `strength` (1.0 keeps the model output, 0.0 keeps the input).
+pub fn blended_tensor_to_image(
+    output: &TensorOutput<f32>,
+    input: Option<&image::Rgb32FImage>,
+    strength: f32,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, ImageProcessingError> {
+    let strength = strength.clamp(0.0, 1.0);
+    let cropped = output_planes(output, 3, width as usize, height as usize)?;
+    let samples = match input {
+        Some(input) => {
+            let mut samples = planar_to_interleaved(cropped, |_, value| value);
+            samples
+                .par_iter_mut()
+                .zip(input.as_raw().par_iter())
+                .for_each(|(value, original)| {
+                    *value = (original + (*value - original) * strength).clamp(0.0, 1.0);
+                });
+            samples
+        }
+        None => planar_to_interleaved(cropped, |_, value| value.clamp(0.0, 1.0)),
+    };
+
+    image::Rgb32FImage::from_raw(width, height, samples)
+        .map(DynamicImage::ImageRgb32F)
+        .ok_or_else(unfilled_image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rgb8_image() -> impl Strategy<Value = image::RgbImage> {
+        (1u32..16, 1u32..16).prop_flat_map(|(width, height)| {
+            proptest::collection::vec(any::<u8>(), (width * height * 3) as usize)
+                .prop_map(move |samples| image::RgbImage::from_raw(width, height, samples).unwrap())
+        })
+    }
 
-    for y in 0..h {
-        for x in 0..w {
-            let to_u8 = |v: T| (v.to_f32() * 255.0).clamp(0.0, 255.0) as u8;
+    fn rgb16_image() -> impl Strategy<Value = image::ImageBuffer<image::Rgb<u16>, Vec<u16>>> {
+        (1u32..16, 1u32..16).prop_flat_map(|(width, height)| {
+            proptest::collection::vec(any::<u16>(), (width * height * 3) as usize).prop_map(
+                move |samples| image::ImageBuffer::from_raw(width, height, samples).unwrap(),
+            )
+        })
+    }
+
+    fn normalization() -> impl Strategy<Value = Normalization> {
+        (
+            prop_oneof![Just(1.0f32), Just(255.0f32)],
+            proptest::array::uniform3(-128.0f32..128.0),
+            proptest::array::uniform3(0.1f32..256.0),
+        )
+            .prop_map(|(scale, mean, std)| Normalization { scale, mean, std })
+    }
+
+    fn tensor(channels: usize) -> impl Strategy<Value = TensorOutput<f32>> {
+        (1usize..12, 1usize..12).prop_flat_map(move |(height, width)| {
+            proptest::collection::vec(-2.0f32..2.0, channels * height * width).prop_map(
+                move |values| {
+                    ndarray::Array4::from_shape_vec((1, channels, height, width), values).unwrap()
+                },
+            )
+        })
+    }
+
+    /// The per-pixel conversions these helpers replaced, as the reference for their results.
+    mod reference {
+        use super::*;
+
+        pub fn normalized_tensor(
+            image: &image::RgbImage,
+            normalization: &Normalization,
+        ) -> TensorInput<f32> {
+            let (width, height) = image.dimensions();
+            ndarray::Array::from_shape_fn(
+                (1, 3, height as usize, width as usize),
+                |(_, c, y, x)| {
+                    let pixel = image.get_pixel(x as u32, y as u32);
+                    (pixel[c] as f32 / normalization.scale - normalization.mean[c])
+                        / normalization.std[c]
+                },
+            )
+        }
+
+        pub fn denormalized_rgb8(
+            tensor: &TensorOutput<f32>,
+            normalization: &Normalization,
+        ) -> image::RgbImage {
+            let (_, _, height, width) = tensor.dim();
+            let mut image = image::RgbImage::new(width as u32, height as u32);
+            for y in 0..height {
+                for x in 0..width {
+                    let value = |c: usize| {
+                        ((tensor[[0, c, y, x]] * normalization.std[c] + normalization.mean[c])
+                            * normalization.scale)
+                            .clamp(0.0, 255.0) as u8
+                    };
+                    image.put_pixel(
+                        x as u32,
+                        y as u32,
+                        image::Rgb([value(0), value(1), value(2)]),
+                    );
+                }
+            }
+            image
+        }
+
+        pub fn image_to_tensor(image: &DynamicImage) -> TensorInput<f32> {
+            let image = image.to_rgb32f();
+            let (width, height) = image.dimensions();
+            ndarray::Array::from_shape_fn(
+                (1, 3, height as usize, width as usize),
+                |(_, c, y, x)| image.get_pixel(x as u32, y as u32)[c],
+            )
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn normalization_matches_per_pixel_conversion(
+            image in rgb8_image(),
+            normalization in normalization(),
+        ) {
+            let tensor = normalized_tensor(&image, &normalization);
+            prop_assert_eq!(tensor, reference::normalized_tensor(&image, &normalization));
+        }
+
+        #[test]
+        fn denormalization_matches_per_pixel_conversion(
+            tensor in tensor(3),
+            normalization in normalization(),
+        ) {
+            let image = denormalized_rgb8(&tensor, &normalization).unwrap();
+            prop_assert_eq!(image, reference::denormalized_rgb8(&tensor, &normalization));
+        }
+
+        #[test]
+        fn image_to_tensor_matches_per_pixel_conversion(image in rgb16_image()) {
+            let image = DynamicImage::ImageRgb16(image);
+            prop_assert_eq!(image_to_tensor::<f32>(&image).unwrap(), reference::image_to_tensor(&image));
+        }
+
+        #[test]
+        fn planes_round_trip_through_interleaving(
+            channels in 1usize..5,
+            (height, width) in (1usize..12, 1usize..12),
+            seed in any::<u8>(),
+        ) {
+            let samples: Vec<u8> = (0..channels * height * width)
+                .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+                .collect();
+
+            let tensor = interleaved_to_planar(&samples, channels, height, width, |_, sample| sample);
+            for ((_, c, y, x), value) in tensor.indexed_iter() {
+                prop_assert_eq!(*value, samples[(y * width + x) * channels + c]);
+            }
+            prop_assert_eq!(planar_to_interleaved(tensor.view(), |_, value| value), samples);
+        }
+
+        #[test]
+        fn interleaving_follows_logical_order(tensor in tensor(3)) {
+            // A view with swapped axes is not contiguous in memory
+            let swapped = tensor.clone().permuted_axes([0, 1, 3, 2]);
+            let samples = planar_to_interleaved(swapped.view(), |_, value| value);
+            let (_, _, _, width) = swapped.dim();
+            for ((_, c, y, x), value) in swapped.indexed_iter() {
+                prop_assert_eq!(*value, samples[(y * width + x) * 3 + c]);
+            }
+        }
 
-            let r = to_u8(tensor[[0, 0, y, x]]);
-            let g = to_u8(tensor[[0, 1, y, x]]);
-            let b = to_u8(tensor[[0, 2, y, x]]);
-            img_buffer.put_pixel(x as u32, y as u32, image::Rgb([r, g, b]));
+        #[test]
+        fn cropping_without_blending_keeps_the_output(
+            tensor in tensor(3),
+            crop in (0.0f64..=1.0, 0.0f64..=1.0),
+        ) {
+            let (_, _, height, width) = tensor.dim();
+            let crop_width = ((width as f64 * crop.0) as u32).max(1);
+            let crop_height = ((height as f64 * crop.1) as u32).max(1);
+
+            let cropped = blended_tensor_to_image(&tensor, None, 1.0, crop_width, crop_height)
+                .unwrap()
+                .into_rgb32f();
+            prop_assert_eq!(cropped.dimensions(), (crop_width, crop_height));
+            for (x, y, pixel) in cropped.enumerate_pixels() {
+                for c in 0..3 {
+                    let expected = tensor[[0, c, y as usize, x as usize]].clamp(0.0, 1.0);
+                    prop_assert_eq!(pixel[c], expected);
+                }
+            }
+        }
+
+        #[test]
+        fn rgb8_round_trips_through_f32(image in rgb8_image()) {
+            let input = DynamicImage::ImageRgb8(image.clone());
+            let tensor = image_to_tensor::<f32>(&input).unwrap();
+            prop_assert_eq!(tensor.dim(), (1, 3, image.height() as usize, image.width() as usize));
+            prop_assert!(tensor.iter().all(|value| (0.0..=1.0).contains(value)));
+
+            let output = tensor_to_image(&tensor).unwrap();
+            prop_assert_eq!(output.to_rgb8(), image);
+        }
+
+        #[test]
+        fn rgb8_round_trips_through_f16(image in rgb8_image()) {
+            let input = DynamicImage::ImageRgb8(image.clone());
+            let tensor = image_to_tensor::<half::f16>(&input).unwrap();
+
+            let output = tensor_to_image(&tensor).unwrap();
+            prop_assert_eq!(output.to_rgb8(), image);
+        }
+
+        #[test]
+        fn rgb16_keeps_its_precision(image in rgb16_image()) {
+            let input = DynamicImage::ImageRgb16(image.clone());
+            let tensor = image_to_tensor::<f32>(&input).unwrap();
+
+            let output = tensor_to_image(&tensor).unwrap();
+            prop_assert_eq!(output.to_rgb16(), image);
+        }
+
+        #[test]
+        fn out_of_range_outputs_are_clamped(
+            values in proptest::collection::vec(-2.0f32..2.0, 3 * 4 * 5),
+        ) {
+            let tensor = ndarray::Array4::from_shape_vec((1, 3, 4, 5), values).unwrap();
+
+            let output = tensor_to_image(&tensor).unwrap().into_rgb32f();
+            prop_assert_eq!(output.dimensions(), (5, 4));
+            for (x, y, pixel) in output.enumerate_pixels() {
+                for c in 0..3 {
+                    let expected = tensor[[0, c, y as usize, x as usize]].clamp(0.0, 1.0);
+                    prop_assert_eq!(pixel[c], expected);
+                }
+            }
+        }
+
+        #[test]
+        fn padding_repeats_the_edge_pixels(image in rgb8_image(), multiple in 1u32..9) {
+            let image = DynamicImage::ImageRgb8(image).into_rgb32f();
+            let (width, height) = image.dimensions();
+
+            let tensor = padded_image_to_tensor(&image, multiple);
+            let (_, _, padded_height, padded_width) = tensor.dim();
+            prop_assert_eq!(padded_width as u32, width.div_ceil(multiple) * multiple);
+            prop_assert_eq!(padded_height as u32, height.div_ceil(multiple) * multiple);
+            for ((_, c, y, x), value) in tensor.indexed_iter() {
+                let source = image.get_pixel((x as u32).min(width - 1), (y as u32).min(height - 1));
+                prop_assert_eq!(*value, source[c]);
+            }
+        }
+
+        #[test]
+        fn blending_interpolates_between_input_and_output(
+            image in rgb8_image(),
+            strength in 0.0f32..=1.0,
+        ) {
+            let input = DynamicImage::ImageRgb8(image.clone()).into_rgb32f();
+            let output = image_to_tensor::<f32>(&DynamicImage::ImageRgb32F(input.clone()))
+                .unwrap()
+                .mapv(|value| 1.0 - value);
+
+            let blended =
+                blended_tensor_to_image(&output, Some(&input), strength, image.width(), image.height())
+                    .unwrap()
+                    .into_rgb32f();
+            for (x, y, pixel) in blended.enumerate_pixels() {
+                for c in 0..3 {
+                    let original = input.get_pixel(x, y)[c];
+                    let expected = original + (1.0 - 2.0 * original) * strength;
+                    prop_assert!((pixel[c] - expected).abs() < 1e-6);
+                }
+            }
         }
     }
 
-    Ok(DynamicImage::ImageRgb8(img_buffer))
+    #[test]
+    fn outputs_with_too_few_channels_are_rejected() {
+        let gray = TensorOutput::<f32>::zeros((1, 1, 4, 4));
+
+        assert!(tensor_to_image(&gray).is_err());
+        assert!(denormalized_rgb8(&gray, &Normalization::unit(1.0)).is_err());
+        assert!(blended_tensor_to_image(&gray, None, 1.0, 4, 4).is_err());
+    }
+
+    #[test]
+    fn outputs_smaller_than_the_image_are_rejected() {
+        let output = TensorOutput::<f32>::zeros((1, 3, 4, 4));
+
+        assert!(blended_tensor_to_image(&output, None, 1.0, 5, 4).is_err());
+        assert!(blended_tensor_to_image(&output, None, 1.0, 4, 5).is_err());
+        assert!(blended_tensor_to_image(&output, None, 1.0, 4, 4).is_ok());
+        assert!(tensor_to_image(&TensorOutput::<f32>::zeros((0, 3, 4, 4))).is_err());
+    }
 }
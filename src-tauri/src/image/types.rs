@@ -1,4 +1,6 @@
 use ndarray::ArrayBase;
+use serde::Deserialize;
+use std::sync::Arc;
 
 #[derive(Default, Clone)]
 pub struct UpscalingParams {}
@@ -19,6 +21,216 @@ pub struct BackgroundRemovalParams {
     pub original_width: Option<u32>,
     pub original_height: Option<u32>,
     pub scaling_factor: Option<f32>,
+    /// Apply the mask to the input as alpha, at the input's size, instead of returning the mask.
+    pub composite: bool,
+    /// The decoded input, kept for compositing so it is only read once.
+    pub original: Option<image::DynamicImage>,
+}
+
+#[derive(Clone)]
+pub struct DenoiseParams {
+    /// Blend between the input (0.0) and the fully denoised output (1.0).
+    pub strength: f32,
+    /// Inputs are padded to a multiple of this size, as required by the model's windows.
+    pub pad_multiple: u32,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    /// The decoded input, kept for blending it with the output.
+    pub input: Option<image::Rgb32FImage>,
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            pad_multiple: 64,
+            original_width: None,
+            original_height: None,
+            input: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ColorizationParams {
+    pub model_width: usize,
+    pub model_height: usize,
+    /// Multiplier for the predicted chroma; 0.0 keeps the image grayscale.
+    pub saturation: f32,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    /// Full-resolution L channel of the input, recombined with the predicted ab channels.
+    pub luminance: Option<Vec<f32>>,
+}
+
+impl Default for ColorizationParams {
+    fn default() -> Self {
+        Self {
+            model_width: 256,
+            model_height: 256,
+            saturation: 1.0,
+            original_width: None,
+            original_height: None,
+            luminance: None,
+        }
+    }
+}
+
+/// Where the inpainting mask comes from. White pixels mark the area to erase.
+#[derive(Clone)]
+pub enum MaskSource {
+    Path(String),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Clone)]
+pub struct InpaintingParams {
+    pub model_width: usize,
+    pub model_height: usize,
+    pub mask: Option<MaskSource>,
+    /// Full-resolution input and mask, used to composite the result so unmasked pixels are kept.
+    pub original: Option<image::DynamicImage>,
+    pub original_mask: Option<image::GrayImage>,
+}
+
+impl Default for InpaintingParams {
+    fn default() -> Self {
+        Self {
+            model_width: 512,
+            model_height: 512,
+            mask: None,
+            original: None,
+            original_mask: None,
+        }
+    }
+}
+
+/// The two tensors fed to an inpainting model.
+pub struct InpaintingInput {
+    pub image: TensorInput<f32>,
+    pub mask: TensorInput<f32>,
+}
+
+#[derive(Clone)]
+pub struct ArtifactRemovalParams {
+    /// JPEG quality (1-100) the input was compressed with, for models that take it as a second
+    /// input. Lower values remove artifacts more aggressively.
+    pub quality: Option<f32>,
+    /// Blend between the input (0.0) and the fully restored output (1.0).
+    pub strength: f32,
+    pub pad_multiple: u32,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    /// The decoded input, kept for blending it with the output.
+    pub input: Option<image::Rgb32FImage>,
+}
+
+impl Default for ArtifactRemovalParams {
+    fn default() -> Self {
+        Self {
+            quality: None,
+            strength: 1.0,
+            pad_multiple: 8,
+            original_width: None,
+            original_height: None,
+            input: None,
+        }
+    }
+}
+
+/// The image tensor plus the optional `[1, 1]` quality factor tensor of FBCNN-style models.
+pub struct ArtifactRemovalInput {
+    pub image: TensorInput<f32>,
+    pub quality: Option<ndarray::Array2<f32>>,
+}
+
+#[derive(Clone)]
+pub struct EnhanceParams {
+    /// How strongly the enhancement is applied; 1.0 is the model's own result.
+    pub intensity: f32,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    /// The decoded input, which the curve maps or the blending are applied to.
+    pub input: Option<image::Rgb32FImage>,
+}
+
+impl Default for EnhanceParams {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            original_width: None,
+            original_height: None,
+            input: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SegmentationParams {
+    pub model_width: usize,
+    pub model_height: usize,
+    /// The decoded input, kept by preprocessing so callers compositing the labels onto it don't
+    /// read it again.
+    pub original: Option<image::DynamicImage>,
+}
+
+impl Default for SegmentationParams {
+    fn default() -> Self {
+        Self {
+            model_width: 512,
+            model_height: 512,
+            original: None,
+        }
+    }
+}
+
+/// A click on the image, in original image pixels.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PromptPoint {
+    pub x: f32,
+    pub y: f32,
+    /// Whether the point marks the object (`true`) or the background to exclude (`false`).
+    pub foreground: bool,
+}
+
+/// A bounding box around the object, in original image pixels.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PromptBox {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+#[derive(Clone)]
+pub struct PromptEncoderParams {
+    /// Length of the longest side the image is resized to before padding to a square.
+    pub input_size: u32,
+}
+
+impl Default for PromptEncoderParams {
+    fn default() -> Self {
+        Self { input_size: 1024 }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PromptDecoderParams {
+    pub embedding: Option<Arc<TensorOutput<f32>>>,
+    pub points: Vec<PromptPoint>,
+    pub prompt_box: Option<PromptBox>,
+    /// Scale from original image pixels to encoder input pixels.
+    pub scale: f32,
+    pub original_width: u32,
+    pub original_height: u32,
+}
+
+/// The decoder inputs of SAM-style models.
+pub struct PromptDecoderInput {
+    pub embedding: Arc<TensorOutput<f32>>,
+    pub point_coords: ndarray::Array3<f32>,
+    pub point_labels: ndarray::Array2<f32>,
+    pub original_size: ndarray::Array1<f32>,
 }
 
 impl Default for FaceRestorationParams {
@@ -39,6 +251,7 @@ pub type TensorOutput<T> = ArrayBase<ndarray::OwnedRepr<T>, ndarray::Dim<[usize;
 
 pub trait NumericType:
     Copy
+    + Default
     + Send
     + Sync
     + std::fmt::Debug
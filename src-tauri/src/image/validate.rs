@@ -0,0 +1,284 @@
+//! Checks run on every input before a model sees it, so corrupt files and decompression bombs
+//! fail with a clear error instead of a panic or an out-of-memory abort.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::decode::{image_dimensions, is_raw, sniff_extension};
+use crate::image::error::ImageProcessingError;
+use crate::utils::{load_json_config, save_json_config};
+
+const LIMITS_CONFIG_FILE: &str = "limits.json";
+/// Enough of the file to recognize every supported format.
+const SNIFF_BYTES: usize = 32;
+const MEGABYTE: u64 = 1024 * 1024;
+
+static INPUT_LIMITS: OnceLock<Mutex<InputLimits>> = OnceLock::new();
+
+/// Configurable bounds on what inputs are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputLimits {
+    pub max_pixels: u64,
+    pub max_file_bytes: u64,
+    /// Upper bound for the estimated memory needed to process an input.
+    pub max_memory_bytes: u64,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self {
+            max_pixels: 100_000_000,
+            max_file_bytes: 512 * MEGABYTE,
+            max_memory_bytes: 8 * 1024 * MEGABYTE,
+        }
+    }
+}
+
+/// Why an input was rejected. Serialized with a `kind` tag so the UI can tell the cases apart.
+#[derive(Debug, Clone, Error, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationError {
+    #[error("{path} does not exist")]
+    NotFound { path: String },
+    #[error("{path} is not an image in a supported format")]
+    UnsupportedFormat { path: String },
+    #[error("{path} could not be read: {reason}")]
+    Corrupt { path: String, reason: String },
+    #[error("The file is {} MB, more than the limit of {} MB", bytes / MEGABYTE, max_bytes / MEGABYTE)]
+    FileTooLarge { bytes: u64, max_bytes: u64 },
+    #[error("The image is {width}x{height} pixels, more than the limit of {max_pixels} pixels")]
+    TooManyPixels {
+        width: u32,
+        height: u32,
+        max_pixels: u64,
+    },
+    #[error(
+        "Processing needs about {} MB of memory, more than the limit of {} MB",
+        required_bytes / MEGABYTE,
+        max_bytes / MEGABYTE
+    )]
+    InsufficientMemory { required_bytes: u64, max_bytes: u64 },
+}
+
+/// What validation found out about an input.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputInfo {
+    /// File extension of the detected format, e.g. `png`.
+    pub format: String,
+    /// For camera RAW files, as read from their metadata.
+    pub dimensions: (u32, u32),
+    pub estimated_memory_bytes: u64,
+}
+
+fn limits_cell() -> &'static Mutex<InputLimits> {
+    INPUT_LIMITS
+        .get_or_init(|| Mutex::new(load_json_config(LIMITS_CONFIG_FILE).unwrap_or_default()))
+}
+
+pub fn input_limits() -> InputLimits {
+    *limits_cell().lock().unwrap()
+}
+
+pub fn set_input_limits(limits: InputLimits) -> Result<(), CommandError> {
+    *limits_cell().lock().unwrap() = limits;
+    info!("Input limits set to {:?}", limits);
+
+    save_json_config(LIMITS_CONFIG_FILE, &limits)
+}
+
+/// Limits for the `image` decoders, so a file whose header lies about its size can't allocate
+/// more than processing it would be allowed to.
+pub fn decoder_limits() -> image::Limits {
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(input_limits().max_memory_bytes);
+    limits
+}
+
+/// Estimated peak memory for processing a `width` x `height` image into a result `output_scale`
+/// times as large: the decoded float image and its input tensor, then the output tensor and the
+/// float and 8-bit output images.
+pub fn estimate_memory(width: u32, height: u32, output_scale: u32) -> u64 {
+    let input_pixels = width as u64 * height as u64;
+    let output_pixels = input_pixels * (output_scale as u64).pow(2);
+    input_pixels * (16 + 12) + output_pixels * (12 + 16 + 4)
+}
+
+/// Validates the image at `path` for an operation whose result is `output_scale` times its size,
+/// reading only the file header.
+pub fn validate(path: &str, output_scale: u32) -> Result<InputInfo, ValidationError> {
+    validate_with_limits(path, output_scale, &input_limits())
+}
+
+fn validate_with_limits(
+    path: &str,
+    output_scale: u32,
+    limits: &InputLimits,
+) -> Result<InputInfo, ValidationError> {
+    let metadata = std::fs::metadata(path).map_err(|_| ValidationError::NotFound {
+        path: path.to_string(),
+    })?;
+    if metadata.len() > limits.max_file_bytes {
+        return Err(ValidationError::FileTooLarge {
+            bytes: metadata.len(),
+            max_bytes: limits.max_file_bytes,
+        });
+    }
+
+    let corrupt = |reason: String| ValidationError::Corrupt {
+        path: path.to_string(),
+        reason,
+    };
+    let mut header = Vec::with_capacity(SNIFF_BYTES);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_BYTES as u64).read_to_end(&mut header))
+        .map_err(|e| corrupt(e.to_string()))?;
+
+    let unsupported = || ValidationError::UnsupportedFormat {
+        path: path.to_string(),
+    };
+    // RAW files are recognized by extension, as most of them look like TIFF
+    let format = if is_raw(path) {
+        if !cfg!(feature = "raw") {
+            return Err(unsupported());
+        }
+        "raw"
+    } else {
+        sniff_extension(&header).ok_or_else(unsupported)?
+    };
+
+    let (width, height) = image_dimensions(path).map_err(|e| match e {
+        ImageProcessingError::Validation(e) => e,
+        e => corrupt(e.to_string()),
+    })?;
+    if width as u64 * height as u64 > limits.max_pixels {
+        return Err(ValidationError::TooManyPixels {
+            width,
+            height,
+            max_pixels: limits.max_pixels,
+        });
+    }
+
+    let required_bytes = estimate_memory(width, height, output_scale);
+    if required_bytes > limits.max_memory_bytes {
+        return Err(ValidationError::InsufficientMemory {
+            required_bytes,
+            max_bytes: limits.max_memory_bytes,
+        });
+    }
+
+    Ok(InputInfo {
+        format: format.to_string(),
+        dimensions: (width, height),
+        estimated_memory_bytes: required_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_png(dir: &std::path::Path, width: u32, height: u32) -> String {
+        let path = dir.join("input.png");
+        image::RgbImage::new(width, height).save(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn accepts_images_within_the_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_png(dir.path(), 30, 20);
+
+        let info = validate_with_limits(&path, 4, &InputLimits::default()).unwrap();
+        assert_eq!(info.format, "png");
+        assert_eq!(info.dimensions, (30, 20));
+        assert_eq!(info.estimated_memory_bytes, estimate_memory(30, 20, 4));
+    }
+
+    #[test]
+    fn rejects_files_over_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_png(dir.path(), 30, 20);
+        let bytes = std::fs::metadata(&path).unwrap().len();
+        let limits = InputLimits {
+            max_file_bytes: bytes - 1,
+            ..Default::default()
+        };
+
+        let result = validate_with_limits(&path, 1, &limits);
+        assert!(matches!(
+            result,
+            Err(ValidationError::FileTooLarge { bytes: b, max_bytes }) if b == bytes && max_bytes == bytes - 1
+        ));
+    }
+
+    #[test]
+    fn rejects_images_over_the_pixel_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_png(dir.path(), 30, 20);
+        let limits = InputLimits {
+            max_pixels: 599,
+            ..Default::default()
+        };
+
+        let result = validate_with_limits(&path, 1, &limits);
+        assert!(matches!(
+            result,
+            Err(ValidationError::TooManyPixels {
+                width: 30,
+                height: 20,
+                max_pixels: 599
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_outputs_that_need_too_much_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_png(dir.path(), 30, 20);
+        let limits = InputLimits {
+            max_memory_bytes: estimate_memory(30, 20, 1),
+            ..Default::default()
+        };
+
+        // The same input fits at its own size but not when upscaled 4x
+        assert!(validate_with_limits(&path, 1, &limits).is_ok());
+        let result = validate_with_limits(&path, 4, &limits);
+        assert!(matches!(
+            result,
+            Err(ValidationError::InsufficientMemory { required_bytes, .. })
+                if required_bytes == estimate_memory(30, 20, 4)
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.png");
+        std::fs::write(&path, b"not an image").unwrap();
+
+        let result = validate_with_limits(path.to_str().unwrap(), 1, &InputLimits::default());
+        assert!(matches!(
+            result,
+            Err(ValidationError::UnsupportedFormat { .. })
+        ));
+    }
+
+    #[cfg(not(feature = "heic"))]
+    #[test]
+    fn rejects_formats_not_built_in_as_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.heic");
+        std::fs::write(&path, b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").unwrap();
+
+        let result = validate_with_limits(path.to_str().unwrap(), 1, &InputLimits::default());
+        assert!(matches!(
+            result,
+            Err(ValidationError::UnsupportedFormat { .. })
+        ));
+    }
+}
@@ -1,15 +1,51 @@
+mod animation;
+mod benchmark;
 mod commands;
-mod image;
+mod error;
+pub mod image;
+mod models;
+mod operation;
+mod output;
+mod server;
 mod utils;
+mod video;
+mod watch;
+mod worker;
+
+/// Synthetic ONNX graphs shared with the integration tests.
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../tests/common/onnx.rs"]
+mod onnx;
 
 use std::error::Error;
 
 use commands::{
+    artifact_removal::{artifact_removal, init_artifact_removal},
     background_removal::{background_removal, init_background_removal},
+    benchmark::{benchmark_models, session_config, set_session_config},
+    colorization::{colorize_image, init_colorization},
+    denoise::{denoise_image, denoise_images, init_denoise},
     download::{check_model_exists, download_models},
+    enhance::{enhance_image, init_enhance},
     face_restoration::{face_restoration, init_face_restoration},
-    image::check_image_dimensions,
+    image::{
+        check_image_dimensions, compare_images, input_limits, set_input_limits, validate_image,
+    },
+    inpainting::{init_inpainting, inpaint_image},
+    models::{load_model, model_status, reload_model, set_model_idle_timeout, unload_model},
+    output::{
+        output_format, preserve_color_profile, set_output_format, set_preserve_color_profile,
+    },
+    prompt_segmentation::{
+        init_prompt_segmentation, prepare_prompt_segmentation, prompt_segmentation,
+    },
+    segmentation::{init_segmentation, segment_image, segmentation_masks},
+    server::{api_server_status, disable_api_server, enable_api_server, regenerate_api_token},
     upscaling::{init_upscaling, upscale_image, upscale_images},
+    video::{ffmpeg_available, process_video},
+    watch::{add_watch_folder, list_watch_folders, remove_watch_folder, watch_history},
+    worker::{cancel_job, set_max_concurrent_jobs, worker_status},
 };
 use tauri::{
     menu::{Menu, MenuItem, SubmenuBuilder},
@@ -41,10 +77,56 @@ pub fn app() -> anyhow::Result<Builder<Wry>> {
             background_removal,
             check_model_exists,
             check_image_dimensions,
+            validate_image,
+            compare_images,
+            input_limits,
+            set_input_limits,
             download_models,
             init_background_removal,
             init_face_restoration,
             init_upscaling,
+            denoise_image,
+            denoise_images,
+            init_denoise,
+            colorize_image,
+            init_colorization,
+            inpaint_image,
+            init_inpainting,
+            artifact_removal,
+            init_artifact_removal,
+            enhance_image,
+            init_enhance,
+            segmentation_masks,
+            segment_image,
+            init_segmentation,
+            prepare_prompt_segmentation,
+            prompt_segmentation,
+            init_prompt_segmentation,
+            list_watch_folders,
+            add_watch_folder,
+            remove_watch_folder,
+            watch_history,
+            api_server_status,
+            enable_api_server,
+            disable_api_server,
+            regenerate_api_token,
+            worker_status,
+            set_max_concurrent_jobs,
+            cancel_job,
+            output_format,
+            set_output_format,
+            preserve_color_profile,
+            set_preserve_color_profile,
+            ffmpeg_available,
+            process_video,
+            model_status,
+            load_model,
+            reload_model,
+            unload_model,
+            set_model_idle_timeout,
+            session_config,
+            set_session_config,
+            benchmark_models,
         ])
         .setup(setup)
         .on_page_load(page_load_handler)
@@ -64,6 +146,10 @@ fn setup(app: &mut App) -> Result<(), Box<dyn Error>> {
 
     setup_menu(app)?;
 
+    watch::init(app.handle().clone())?;
+    server::init();
+    models::init()?;
+
     let mut builder = WebviewWindowBuilder::new(app, "main", WebviewUrl::default());
 
     #[cfg(desktop)]
@@ -198,6 +284,11 @@ fn setup_menu<R: Runtime>(app: &mut App<R>) -> Result<(), Box<dyn Error>> {
         info!("menu event: {:?}", event);
         match event.id.as_ref() {
             "open" => open_main(app).unwrap(),
+            "hide" => {
+                if let Some(window) = app.get_webview_window("main") {
+                    window.hide().unwrap();
+                }
+            }
             "about" => {
                 app.dialog()
                     .message(format!(
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::commands::{
+    artifact_removal::ARTIFACT_REMOVAL_MODEL,
+    background_removal::BACKGROUND_REMOVAL_MODEL,
+    colorization::COLORIZATION_MODEL,
+    denoise::DENOISE_MODEL,
+    enhance::ENHANCE_MODEL,
+    face_restoration::FACE_RESTORATION_MODEL,
+    inpainting::INPAINTING_MODEL,
+    prompt_segmentation::{PROMPT_DECODER_MODEL, PROMPT_ENCODER_MODEL},
+    segmentation::SEGMENTATION_MODEL,
+    upscaling::UPSCALE_MODEL,
+};
+use crate::error::CommandError;
+use crate::image::manager::ManagedModel;
+use crate::utils::{load_json_config, save_json_config};
+
+const MODELS_CONFIG_FILE: &str = "models.json";
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+static IDLE_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModelsConfig {
+    /// Unload a model after it has been unused for this many seconds. `None` keeps models loaded.
+    idle_timeout_secs: Option<u64>,
+}
+
+/// Every model the app can load.
+pub fn managed_models() -> [&'static dyn ManagedModel; 11] {
+    [
+        &UPSCALE_MODEL,
+        &FACE_RESTORATION_MODEL,
+        &BACKGROUND_REMOVAL_MODEL,
+        &DENOISE_MODEL,
+        &COLORIZATION_MODEL,
+        &INPAINTING_MODEL,
+        &ARTIFACT_REMOVAL_MODEL,
+        &ENHANCE_MODEL,
+        &SEGMENTATION_MODEL,
+        &PROMPT_ENCODER_MODEL,
+        &PROMPT_DECODER_MODEL,
+    ]
+}
+
+pub fn find_model(name: &str) -> Result<&'static dyn ManagedModel, CommandError> {
+    managed_models()
+        .into_iter()
+        .find(|model| model.name() == name)
+        .ok_or_else(|| CommandError::InvalidArgument(format!("Unknown model: {}", name)))
+}
+
+/// Restores the idle timeout and starts unloading models that stay unused past it.
+pub fn init() -> Result<(), CommandError> {
+    *IDLE_TIMEOUT.lock().unwrap() = load_config().idle_timeout_secs.map(Duration::from_secs);
+
+    thread::Builder::new()
+        .name("model-idle-unload".to_string())
+        .spawn(|| loop {
+            thread::sleep(IDLE_CHECK_INTERVAL);
+            if let Some(timeout) = idle_timeout() {
+                for model in managed_models() {
+                    model.unload_if_idle(timeout);
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+pub fn idle_timeout() -> Option<Duration> {
+    *IDLE_TIMEOUT.lock().unwrap()
+}
+
+pub fn set_idle_timeout(timeout: Option<Duration>) -> Result<(), CommandError> {
+    *IDLE_TIMEOUT.lock().unwrap() = timeout;
+    save_config(&ModelsConfig {
+        idle_timeout_secs: timeout.map(|timeout| timeout.as_secs()),
+    })
+}
+
+fn load_config() -> ModelsConfig {
+    load_json_config(MODELS_CONFIG_FILE).unwrap_or_default()
+}
+
+fn save_config(config: &ModelsConfig) -> Result<(), CommandError> {
+    save_json_config(MODELS_CONFIG_FILE, config)
+}
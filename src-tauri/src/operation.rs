@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::animation;
+use crate::commands::{
+    artifact_removal::remove_artifacts, background_removal::remove_background,
+    colorization::colorize, denoise::denoise, enhance::enhance, face_restoration::restore_faces,
+    upscaling::upscale,
+};
+use crate::error::CommandError;
+use crate::image::model::{ImageModel, UpscalingModel};
+use crate::image::processor::{Progress, ProgressFn};
+use crate::output::with_intermediate_format;
+use crate::utils::cache_dir;
+
+const PIPELINE_DIR: &str = "pipeline";
+
+/// A single processing step that can be chained into a pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Upscale,
+    FaceRestoration,
+    BackgroundRemoval,
+    Denoise,
+    Colorize,
+    RemoveArtifacts,
+    Enhance,
+}
+
+impl Operation {
+    /// How many times larger than the input the result is.
+    pub fn output_scale(self) -> u32 {
+        match self {
+            Operation::Upscale => UpscalingModel::OUTPUT_SCALE,
+            _ => 1,
+        }
+    }
+
+    /// Runs the operation on `input_path`, writing the result into `output_dir`.
+    pub fn run(self, input_path: &str, output_dir: &str) -> Result<String, CommandError> {
+        self.run_with_progress(input_path, output_dir, &|_| {})
+    }
+
+    /// Runs the operation, frame by frame for animated inputs.
+    pub fn run_with_progress(
+        self,
+        input_path: &str,
+        output_dir: &str,
+        progress: &ProgressFn,
+    ) -> Result<String, CommandError> {
+        animation::run(
+            input_path,
+            output_dir,
+            progress,
+            |input, output, progress| self.run_still(input, output, progress),
+        )
+    }
+
+    fn run_still(
+        self,
+        input_path: &str,
+        output_dir: &str,
+        progress: &ProgressFn,
+    ) -> Result<String, CommandError> {
+        match self {
+            Operation::Upscale => upscale(input_path, output_dir, progress),
+            Operation::FaceRestoration => restore_faces(input_path, output_dir, progress),
+            Operation::BackgroundRemoval => remove_background(input_path, output_dir, progress),
+            Operation::Denoise => denoise(input_path, output_dir, None, progress),
+            Operation::Colorize => colorize(input_path, output_dir, None, progress),
+            Operation::RemoveArtifacts => {
+                remove_artifacts(input_path, output_dir, None, None, progress)
+            }
+            Operation::Enhance => enhance(input_path, output_dir, None, progress),
+        }
+    }
+}
+
+/// Runs `pipeline` step by step, feeding each output into the next step.
+///
+/// Intermediate results are written to the cache directory; only the final step writes into
+/// `output_dir`. Returns the path of the final output.
+pub fn run_pipeline(
+    pipeline: &[Operation],
+    input_path: &str,
+    output_dir: &str,
+) -> Result<String, CommandError> {
+    run_pipeline_with_progress(pipeline, input_path, output_dir, &|_| {})
+}
+
+/// Like [`run_pipeline`], reporting progress over the whole pipeline with each step weighted
+/// equally. Animated inputs run the whole pipeline on each frame.
+pub fn run_pipeline_with_progress(
+    pipeline: &[Operation],
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    animation::run(
+        input_path,
+        output_dir,
+        progress,
+        |input, output, progress| run_still_pipeline(pipeline, input, output, progress),
+    )
+}
+
+fn run_still_pipeline(
+    pipeline: &[Operation],
+    input_path: &str,
+    output_dir: &str,
+    progress: &ProgressFn,
+) -> Result<String, CommandError> {
+    let Some((last, steps)) = pipeline.split_last() else {
+        return Err(CommandError::InvalidArgument(
+            "Pipeline is empty".to_string(),
+        ));
+    };
+
+    let intermediate_dir = cache_dir()
+        .join(PIPELINE_DIR)
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&intermediate_dir)?;
+
+    let total = pipeline.len() as f32;
+    let step_progress = |index: usize| {
+        move |step: Progress| {
+            progress(Progress {
+                stage: step.stage,
+                fraction: (index as f32 + step.fraction) / total,
+            })
+        }
+    };
+
+    let mut current = input_path.to_string();
+    let mut result = Ok(());
+    for (index, step) in steps.iter().enumerate() {
+        match with_intermediate_format(|| {
+            step.run_still(
+                &current,
+                intermediate_dir.to_str().unwrap(),
+                &step_progress(index),
+            )
+        }) {
+            Ok(output) => current = output,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    let output =
+        result.and_then(|_| last.run_still(&current, output_dir, &step_progress(steps.len())));
+
+    let _ = std::fs::remove_dir_all(&intermediate_dir);
+    output
+}
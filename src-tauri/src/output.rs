@@ -0,0 +1,277 @@
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, DynamicImage, ImageEncoder, ImageError, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::decode::is_raw;
+use crate::image::icc::{embed_in_jpeg, embed_in_png, from_srgb, read_profile};
+use crate::image::ImageProcessingError;
+use crate::utils::{load_json_config, output_path, save_json_config};
+
+const OUTPUT_CONFIG_FILE: &str = "output.json";
+const JPEG_QUALITY: u8 = 95;
+const WEBP_QUALITY: f32 = 90.0;
+const AVIF_QUALITY: u8 = 80;
+/// rav1e speed from 1 (slowest) to 10; 6 keeps encoding of large images within seconds.
+const AVIF_SPEED: u8 = 6;
+
+static OUTPUT_CONFIG: OnceLock<Mutex<OutputConfig>> = OnceLock::new();
+
+thread_local! {
+    /// Format of the job running on this thread, taking precedence over the setting.
+    static JOB_FORMAT: Cell<Option<OutputFormat>> = const { Cell::new(None) };
+}
+
+/// File format and bit depth of processed images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// PNG matching the input: 16-bit for 16-bit inputs, OpenEXR for floating point (HDR)
+    /// inputs and 8-bit otherwise.
+    #[default]
+    Auto,
+    Png8,
+    Png16,
+    Tiff16,
+    Exr,
+    /// 8-bit JPEG at high quality, without transparency.
+    Jpeg,
+    /// 8-bit AVIF, lossy.
+    Avif,
+    WebpLossless,
+    WebpLossy,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct OutputConfig {
+    format: OutputFormat,
+    /// Convert results back to the input's ICC profile and embed it, instead of writing sRGB.
+    preserve_color_profile: bool,
+}
+
+fn config_cell() -> &'static Mutex<OutputConfig> {
+    OUTPUT_CONFIG
+        .get_or_init(|| Mutex::new(load_json_config(OUTPUT_CONFIG_FILE).unwrap_or_default()))
+}
+
+pub fn output_format() -> OutputFormat {
+    config_cell().lock().unwrap().format
+}
+
+pub fn set_output_format(format: OutputFormat) -> Result<(), CommandError> {
+    info!("Output format set to {:?}", format);
+    update_config(|config| config.format = format)
+}
+
+pub fn preserve_color_profile() -> bool {
+    config_cell().lock().unwrap().preserve_color_profile
+}
+
+pub fn set_preserve_color_profile(enabled: bool) -> Result<(), CommandError> {
+    info!("Preserving color profiles: {}", enabled);
+    update_config(|config| config.preserve_color_profile = enabled)
+}
+
+fn update_config(update: impl FnOnce(&mut OutputConfig)) -> Result<(), CommandError> {
+    let config = {
+        let mut config = config_cell().lock().unwrap();
+        update(&mut config);
+        *config
+    };
+
+    save_json_config(OUTPUT_CONFIG_FILE, &config)
+}
+
+/// Restores the previous job format when a job finishes, even by panicking.
+struct JobFormatGuard(Option<OutputFormat>);
+
+impl Drop for JobFormatGuard {
+    fn drop(&mut self) {
+        JOB_FORMAT.with(|cell| cell.set(self.0));
+    }
+}
+
+/// Runs `job` with every output it saves on this thread written as `format` instead of the
+/// configured format. `None` keeps the format that is already in effect.
+pub(crate) fn with_output_format<R>(format: Option<OutputFormat>, job: impl FnOnce() -> R) -> R {
+    let previous = JOB_FORMAT.with(|cell| cell.get());
+    let _guard = JobFormatGuard(previous);
+    JOB_FORMAT.with(|cell| cell.set(format.or(previous)));
+    job()
+}
+
+/// Runs `job` with its outputs written losslessly in a format every step can read back, for
+/// intermediate results such as pipeline steps and animation frames.
+pub(crate) fn with_intermediate_format<R>(job: impl FnOnce() -> R) -> R {
+    with_output_format(Some(OutputFormat::Auto), job)
+}
+
+/// Picks the concrete format for `Auto` from the input's sample type; RAW inputs get 16-bit TIFF.
+fn resolve(format: OutputFormat, input_path: &str) -> OutputFormat {
+    if format != OutputFormat::Auto {
+        return format;
+    }
+    if is_raw(input_path) {
+        return OutputFormat::Tiff16;
+    }
+
+    let color_type = ImageReader::open(input_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .map(|decoder| image::ImageDecoder::color_type(&decoder));
+
+    match color_type {
+        Some(ColorType::Rgb32F | ColorType::Rgba32F) => OutputFormat::Exr,
+        Some(ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16) => {
+            OutputFormat::Png16
+        }
+        _ => OutputFormat::Png8,
+    }
+}
+
+/// Saves a processed image of `input_path` into `output_dir` in the configured output format,
+/// returning the output path.
+pub(crate) fn save_output(
+    image: &DynamicImage,
+    input_path: &str,
+    output_dir: &str,
+    suffix: &str,
+) -> Result<String, CommandError> {
+    let format = JOB_FORMAT
+        .with(|cell| cell.get())
+        .unwrap_or_else(output_format);
+    let format = resolve(format, input_path);
+    let alpha = image.color().has_alpha();
+
+    let (extension, converted) = match format {
+        OutputFormat::Auto
+        | OutputFormat::Png8
+        | OutputFormat::Avif
+        | OutputFormat::WebpLossless
+        | OutputFormat::WebpLossy => {
+            let extension = match format {
+                OutputFormat::Avif => "avif",
+                OutputFormat::WebpLossless | OutputFormat::WebpLossy => "webp",
+                _ => "png",
+            };
+            if alpha {
+                (extension, DynamicImage::ImageRgba8(image.to_rgba8()))
+            } else {
+                (extension, DynamicImage::ImageRgb8(image.to_rgb8()))
+            }
+        }
+        OutputFormat::Png16 | OutputFormat::Tiff16 => {
+            let extension = if format == OutputFormat::Png16 {
+                "png"
+            } else {
+                "tif"
+            };
+            if alpha {
+                (extension, DynamicImage::ImageRgba16(image.to_rgba16()))
+            } else {
+                (extension, DynamicImage::ImageRgb16(image.to_rgb16()))
+            }
+        }
+        OutputFormat::Exr if alpha => ("exr", DynamicImage::ImageRgba32F(image.to_rgba32f())),
+        OutputFormat::Exr => ("exr", DynamicImage::ImageRgb32F(image.to_rgb32f())),
+        OutputFormat::Jpeg => ("jpg", DynamicImage::ImageRgb8(image.to_rgb8())),
+    };
+
+    let output_path = output_path(input_path, output_dir, suffix).with_extension(extension);
+
+    let profile = preserve_color_profile()
+        .then(|| read_profile(input_path))
+        .flatten();
+    match profile {
+        Some(_) if !carries_profile(format) => {
+            info!("{:?} can't embed a color profile, writing sRGB", format);
+            write(&converted, format, &output_path)?;
+        }
+        Some(icc) => {
+            let converted = from_srgb(converted, &icc)?;
+            write_with_profile(&converted, format, &output_path, &icc)?;
+        }
+        None => write(&converted, format, &output_path)?,
+    }
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+/// Whether `format` is written with an embedded ICC profile when color profiles are preserved.
+fn carries_profile(format: OutputFormat) -> bool {
+    matches!(
+        format,
+        OutputFormat::Auto
+            | OutputFormat::Png8
+            | OutputFormat::Png16
+            | OutputFormat::Jpeg
+            | OutputFormat::WebpLossless
+    )
+}
+
+fn write(image: &DynamicImage, format: OutputFormat, path: &Path) -> Result<(), ImageError> {
+    let file = || File::create(path).map(BufWriter::new);
+    match format {
+        OutputFormat::Jpeg => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(file()?, JPEG_QUALITY))
+        }
+        OutputFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            file()?,
+            AVIF_SPEED,
+            AVIF_QUALITY,
+        )),
+        // The image crate only encodes lossless WebP
+        OutputFormat::WebpLossy => {
+            let (width, height) = (image.width(), image.height());
+            let encoder = if image.color().has_alpha() {
+                webp::Encoder::from_rgba(image.as_bytes(), width, height)
+            } else {
+                webp::Encoder::from_rgb(image.as_bytes(), width, height)
+            };
+            std::fs::write(path, &*encoder.encode(WEBP_QUALITY)).map_err(ImageError::IoError)
+        }
+        _ => image.save(path),
+    }
+}
+
+/// Writes `image` with the `icc` profile embedded, for the formats in [`carries_profile`].
+fn write_with_profile(
+    image: &DynamicImage,
+    format: OutputFormat,
+    path: &Path,
+    icc: &[u8],
+) -> Result<(), ImageProcessingError> {
+    let encoded = match format {
+        OutputFormat::Jpeg => {
+            let mut jpeg = Vec::new();
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY))?;
+            embed_in_jpeg(&jpeg, icc)?
+        }
+        OutputFormat::WebpLossless => {
+            let mut webp = Vec::new();
+            let mut encoder = WebPEncoder::new_lossless(&mut webp);
+            encoder
+                .set_icc_profile(icc.to_vec())
+                .map_err(|e| ImageProcessingError::Processing(e.to_string()))?;
+            image.write_with_encoder(encoder)?;
+            webp
+        }
+        _ => {
+            let mut png = Vec::new();
+            image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            embed_in_png(&png, icc)?
+        }
+    };
+    std::fs::write(path, encoded)?;
+    Ok(())
+}
@@ -0,0 +1,546 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::error::CommandError;
+use crate::image::decode::sniff_extension;
+use crate::operation::Operation;
+use crate::output::{with_output_format, OutputFormat};
+use crate::utils::{cache_dir, load_json_config, save_json_config};
+use crate::worker::run_blocking;
+
+const API_CONFIG_FILE: &str = "api_server.json";
+const API_DIR: &str = "api";
+const DEFAULT_PORT: u16 = 8023;
+const MAX_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+static API_SERVER: Mutex<Option<RunningServer>> = Mutex::new(None);
+
+/// Persisted settings of the local HTTP API. The server is disabled unless explicitly enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiServerConfig {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+impl Default for ApiServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: generate_token(),
+        }
+    }
+}
+
+/// Runs an operation on the input file at the first path, writing into the directory at the
+/// second and returning the output path.
+type Runner = Arc<dyn Fn(Operation, &str, &str) -> Result<String, CommandError> + Send + Sync>;
+
+#[derive(Clone)]
+struct ApiState {
+    runner: Runner,
+    /// Where uploads and their results are written while a request runs.
+    work_dir: PathBuf,
+}
+
+struct RunningServer {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<CommandError> for ApiError {
+    fn from(error: CommandError) -> Self {
+        match error {
+            CommandError::Validation(_) | CommandError::InvalidArgument(_) => {
+                Self::bad_request(error.to_string())
+            }
+            _ => Self::internal(error.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.message });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Builds the API routes. Every route requires `Authorization: Bearer <token>`.
+pub fn router(token: String) -> Router {
+    let state = ApiState {
+        runner: Arc::new(|operation, input_path, output_dir| operation.run(input_path, output_dir)),
+        work_dir: cache_dir().join(API_DIR),
+    };
+    router_with_state(token, state)
+}
+
+fn router_with_state(token: String, state: ApiState) -> Router {
+    Router::new()
+        .route("/v1/upscale", post(upscale))
+        .route("/v1/face-restoration", post(face_restoration))
+        .route("/v1/background-removal", post(background_removal))
+        .route("/v1/denoise", post(denoise))
+        .route("/v1/colorize", post(colorize))
+        .route("/v1/artifact-removal", post(artifact_removal))
+        .route("/v1/enhance", post(enhance))
+        .with_state(state)
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .layer(middleware::from_fn_with_state(Arc::new(token), authorize))
+}
+
+/// Starts the server in the background if it was enabled in a previous session.
+pub fn init() {
+    let config = load_config();
+    if !config.enabled {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = start(&config).await {
+            warn!("Failed to start API server: {}", e);
+        }
+    });
+}
+
+/// Starts the server on 127.0.0.1 with `config`, replacing any running instance.
+pub async fn start(config: &ApiServerConfig) -> Result<(), CommandError> {
+    // The old listener must be closed before its port can be bound again
+    stop().await;
+
+    let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, config.port)).await?;
+    let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+    let app = router(config.token.clone());
+
+    let task = tauri::async_runtime::spawn(async move {
+        let result = axum::serve(listener, app)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            })
+            .await;
+        if let Err(e) = result {
+            warn!("API server stopped with error: {}", e);
+        }
+    });
+
+    info!("API server listening on 127.0.0.1:{}", config.port);
+    *API_SERVER.lock().unwrap() = Some(RunningServer {
+        port: config.port,
+        shutdown,
+        task,
+    });
+    Ok(())
+}
+
+/// Stops the running server, returning once it has finished its requests and freed its port.
+pub async fn stop() {
+    let server = API_SERVER.lock().unwrap().take();
+    if let Some(server) = server {
+        info!("Stopping API server on port {}", server.port);
+        let _ = server.shutdown.send(());
+        let _ = server.task.await;
+    }
+}
+
+/// Returns the port of the running server, if any.
+pub fn running_port() -> Option<u16> {
+    API_SERVER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|server| server.port)
+}
+
+/// Loads the saved settings, persisting defaults on first use so the generated token is stable.
+pub fn load_config() -> ApiServerConfig {
+    if let Some(config) = load_json_config(API_CONFIG_FILE) {
+        return config;
+    }
+
+    let config = ApiServerConfig::default();
+    if let Err(e) = save_config(&config) {
+        warn!("Failed to save API server settings: {}", e);
+    }
+    config
+}
+
+pub fn save_config(config: &ApiServerConfig) -> Result<(), CommandError> {
+    save_json_config(API_CONFIG_FILE, config)
+}
+
+pub fn generate_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+async fn authorize(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes()));
+
+    if !authorized {
+        return ApiError {
+            status: StatusCode::UNAUTHORIZED,
+            message: "Missing or invalid token".to_string(),
+        }
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn upscale(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::Upscale, multipart).await
+}
+
+async fn face_restoration(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::FaceRestoration, multipart).await
+}
+
+async fn background_removal(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::BackgroundRemoval, multipart).await
+}
+
+async fn denoise(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::Denoise, multipart).await
+}
+
+async fn colorize(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::Colorize, multipart).await
+}
+
+async fn artifact_removal(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::RemoveArtifacts, multipart).await
+}
+
+async fn enhance(
+    State(state): State<ApiState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, Operation::Enhance, multipart).await
+}
+
+async fn process(
+    state: ApiState,
+    operation: Operation,
+    mut multipart: Multipart,
+) -> Result<Response, ApiError> {
+    let (bytes, format) = read_fields(&mut multipart).await?;
+
+    let (output, mime_type) = run_blocking(move || {
+        with_output_format(format, || run_operation(&state, operation, &bytes))
+    })
+    .await??;
+
+    Ok(([(header::CONTENT_TYPE, mime_type)], output).into_response())
+}
+
+/// Returns the content of the `image` multipart field and the output format requested by the
+/// optional `format` field (e.g. `avif` or `webp_lossy`).
+async fn read_fields(
+    multipart: &mut Multipart,
+) -> Result<(Vec<u8>, Option<OutputFormat>), ApiError> {
+    let mut image = None;
+    let mut format = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?
+    {
+        match field.name() {
+            Some("image") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+                image = Some(bytes.to_vec());
+            }
+            Some("format") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+                let parsed = serde_json::from_value(serde_json::Value::String(text.clone()))
+                    .map_err(|_| {
+                        ApiError::bad_request(format!("Unknown output format: {}", text))
+                    })?;
+                format = Some(parsed);
+            }
+            _ => {}
+        }
+    }
+
+    let image = image.ok_or_else(|| ApiError::bad_request("Missing multipart field: image"))?;
+    Ok((image, format))
+}
+
+/// Runs `operation` on an uploaded image, returning the output and its MIME type (which follows
+/// the output format setting and animated inputs).
+fn run_operation(
+    state: &ApiState,
+    operation: Operation,
+    bytes: &[u8],
+) -> Result<(Vec<u8>, &'static str), ApiError> {
+    let extension =
+        sniff_extension(bytes).ok_or_else(|| ApiError::bad_request("Unsupported image format"))?;
+
+    let job_dir = state.work_dir.join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&job_dir).map_err(|e| ApiError::internal(e.to_string()))?;
+
+    let input_path = job_dir.join(format!("input.{}", extension));
+    let result = std::fs::write(&input_path, bytes)
+        .map_err(CommandError::from)
+        .and_then(|_| {
+            (state.runner)(
+                operation,
+                input_path.to_str().unwrap(),
+                job_dir.to_str().unwrap(),
+            )
+        })
+        .and_then(|output_path| {
+            let mime_type = ImageFormat::from_path(&output_path)
+                .map_or("application/octet-stream", |format| format.to_mime_type());
+            let output = std::fs::read(output_path)?;
+            Ok((output, mime_type))
+        });
+
+    let _ = std::fs::remove_dir_all(&job_dir);
+    result.map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::model::UpscalingModel;
+    use crate::image::processor::ModelProcessor;
+    use crate::image::types::UpscalingParams;
+    use crate::onnx;
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use std::io::Cursor;
+    use std::path::Path;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "test-token";
+    const BOUNDARY: &str = "imagenie-boundary";
+
+    /// Routes that upscale with a synthetic 2x model from `dir` and work in `dir`, so requests run
+    /// end to end without the real models or the app directory.
+    fn test_router(dir: &Path) -> Router {
+        let model = dir.join("upscale.onnx");
+        std::fs::write(&model, onnx::nearest_upscale_2x()).unwrap();
+        let processor = ModelProcessor::<UpscalingModel>::new(model.to_str().unwrap()).unwrap();
+
+        let runner: Runner = Arc::new(move |operation, input_path, output_dir| {
+            assert_eq!(operation, Operation::Upscale);
+            let image = processor.process_single(input_path, &UpscalingParams::default())?;
+            let output_path = Path::new(output_dir).join("output.png");
+            image.save(&output_path)?;
+            Ok(output_path.to_str().unwrap().to_string())
+        });
+        let state = ApiState {
+            runner,
+            work_dir: dir.join("work"),
+        };
+        router_with_state(TOKEN.to_string(), state)
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 40) as u8, (y * 40) as u8, 128])
+        });
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn multipart_request(token: Option<&str>, field: &str, content: &[u8]) -> Request {
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{field}\"; filename=\"a.png\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(content);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+        let mut builder = Request::post("/v1/upscale").header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        );
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    async fn error_message(response: Response) -> String {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["error"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_token() {
+        let response = router(TOKEN.to_string())
+            .oneshot(multipart_request(None, "image", b"data"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_token() {
+        let response = router(TOKEN.to_string())
+            .oneshot(multipart_request(Some("wrong"), "image", b"data"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn rejects_request_without_image_field() {
+        let response = router(TOKEN.to_string())
+            .oneshot(multipart_request(Some(TOKEN), "file", b"data"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error_message(response).await,
+            "Missing multipart field: image"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_image_format() {
+        let response = router(TOKEN.to_string())
+            .oneshot(multipart_request(Some(TOKEN), "image", b"not an image"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_message(response).await, "Unsupported image format");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_output_format() {
+        let response = router(TOKEN.to_string())
+            .oneshot(multipart_request(Some(TOKEN), "format", b"bmp"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_message(response).await, "Unknown output format: bmp");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn upscales_an_uploaded_image() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let response = test_router(dir.path())
+            .oneshot(multipart_request(Some(TOKEN), "image", &png(6, 4)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let output = image::load_from_memory(&body).unwrap();
+        assert_eq!((output.width(), output.height()), (12, 8));
+        // The upload and its result are removed once the response is built
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("work")).unwrap().count(),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn restarting_rebinds_the_same_port() {
+        let config = ApiServerConfig {
+            enabled: true,
+            port: free_port(),
+            token: TOKEN.to_string(),
+        };
+
+        start(&config).await.unwrap();
+        start(&config).await.unwrap();
+        assert_eq!(running_port(), Some(config.port));
+
+        stop().await;
+        assert_eq!(running_port(), None);
+        std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, config.port)).unwrap();
+    }
+}
@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::error::CommandError;
 
 const APP_DIR: &str = ".imagenie";
 const LOG_DIR: &str = "logs";
@@ -19,19 +23,16 @@ pub(crate) fn log_dir() -> PathBuf {
     app_dir().join(LOG_DIR)
 }
 
-#[allow(unused)]
 #[inline]
 pub(crate) fn cache_dir() -> PathBuf {
     app_dir().join(CACHE_DIR)
 }
 
-#[allow(unused)]
 #[inline]
 pub(crate) fn db_dir() -> PathBuf {
     app_dir().join(DB_DIR)
 }
 
-#[allow(unused)]
 #[inline]
 pub(crate) fn config_dir() -> PathBuf {
     app_dir().join(CONFIG_DIR)
@@ -41,3 +42,36 @@ pub(crate) fn config_dir() -> PathBuf {
 pub(crate) fn models_dir() -> PathBuf {
     app_dir().join(MODELS_DIR)
 }
+
+/// Reads the settings saved as `file_name` in the config directory. Returns `None` if there are
+/// none yet, or if the file is invalid, which is logged.
+pub(crate) fn load_json_config<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+    let path = config_dir().join(file_name);
+    let content = std::fs::read_to_string(&path).ok()?;
+
+    serde_json::from_str(&content)
+        .inspect_err(|e| warn!("Ignoring invalid {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Saves `config` as `file_name` in the config directory.
+pub(crate) fn save_json_config<T: Serialize + ?Sized>(
+    file_name: &str,
+    config: &T,
+) -> Result<(), CommandError> {
+    let dir = config_dir();
+    std::fs::create_dir_all(&dir)?;
+    let content =
+        serde_json::to_string_pretty(config).map_err(|e| CommandError::Io(e.to_string()))?;
+    Ok(std::fs::write(dir.join(file_name), content)?)
+}
+
+/// Builds `<output_dir>/<input stem>_<suffix>.png` for a processed image.
+pub(crate) fn output_path(input_path: &str, output_dir: &str, suffix: &str) -> PathBuf {
+    let name = Path::new(input_path)
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    Path::new(output_dir).join(format!("{}_{}.png", name, suffix))
+}
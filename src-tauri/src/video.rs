@@ -0,0 +1,417 @@
+//! Video processing through a local ffmpeg binary.
+//!
+//! Frames are extracted into a cache directory keyed by the input file and pipeline, processed
+//! one by one, and encoded back together with the original audio. Processed frames survive an
+//! interrupted or cancelled job, so running the same job again continues where it stopped.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::image::processor::{ProcessingStage, Progress, ProgressFn};
+use crate::operation::{run_pipeline_with_progress, Operation};
+use crate::output::with_intermediate_format;
+use crate::utils::{app_dir, cache_dir};
+use crate::worker::CancelToken;
+
+const VIDEO_DIR: &str = "video";
+const FRAMES_DIR: &str = "frames";
+const PROCESSED_DIR: &str = "processed";
+/// Written once all frames are extracted, so a partial extraction is redone.
+const EXTRACTED_MARKER: &str = "extracted";
+const DEFAULT_FRAME_RATE: f32 = 30.0;
+/// Share of the progress spent extracting and encoding; the rest goes to the frames.
+const EXTRACT_WEIGHT: f32 = 0.05;
+const ENCODE_WEIGHT: f32 = 0.05;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[cfg(windows)]
+const FFMPEG_BINARY: &str = "ffmpeg.exe";
+#[cfg(not(windows))]
+const FFMPEG_BINARY: &str = "ffmpeg";
+
+/// What ffmpeg reports about the input.
+#[derive(Debug, Clone)]
+struct VideoInfo {
+    frame_rate: f32,
+    has_audio: bool,
+}
+
+/// Finds ffmpeg next to the executable (bundled), in the app's `bin` directory, or on `PATH`.
+pub fn find_ffmpeg() -> Option<PathBuf> {
+    let bundled = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(FFMPEG_BINARY)));
+    let app = app_dir().join("bin").join(FFMPEG_BINARY);
+    let on_path = std::env::var_os("PATH")
+        .into_iter()
+        .flat_map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .map(|dir| dir.join(FFMPEG_BINARY));
+
+    bundled
+        .into_iter()
+        .chain(std::iter::once(app))
+        .chain(on_path)
+        .find(|path| path.is_file())
+}
+
+fn ffmpeg_command(ffmpeg: &Path) -> Command {
+    let mut command = Command::new(ffmpeg);
+    command.args(["-hide_banner", "-nostdin", "-y"]);
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so no console pops up for every job
+        command.creation_flags(0x0800_0000);
+    }
+
+    command
+}
+
+/// Runs an ffmpeg command to completion, killing it if the job gets cancelled. Commands should
+/// pass `-loglevel error`, as stderr is only read once ffmpeg exits.
+fn run_ffmpeg(mut command: Command, cancel: &CancelToken) -> Result<(), CommandError> {
+    let mut child = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CommandError::Ffmpeg(format!("it could not be started: {}", e)))?;
+
+    loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return cancel.check();
+        }
+        if child.try_wait()?.is_some() {
+            break;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(CommandError::Ffmpeg(
+            stderr.lines().last().unwrap_or_default().to_string(),
+        ))
+    }
+}
+
+/// Reads the frame rate and whether there is an audio track from `ffmpeg -i`.
+fn probe(ffmpeg: &Path, input_path: &str) -> Result<VideoInfo, CommandError> {
+    let output = ffmpeg_command(ffmpeg)
+        .args(["-i", input_path])
+        .output()
+        .map_err(|e| CommandError::Ffmpeg(format!("it could not be started: {}", e)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let video = stderr
+        .lines()
+        .find(|line| line.contains("Video:"))
+        .ok_or_else(|| {
+            CommandError::InvalidArgument(format!("No video stream found in {}", input_path))
+        })?;
+    // e.g. "Stream #0:0: Video: h264 ..., 1920x1080, 29.97 fps, 29.97 tbr, ..."
+    let rate = |unit: &str| {
+        video
+            .split(',')
+            .filter_map(|part| part.trim().strip_suffix(unit))
+            .find_map(|value| value.trim().parse::<f32>().ok())
+    };
+    let frame_rate = rate(" fps")
+        .or_else(|| rate(" tbr"))
+        .filter(|rate| *rate > 0.0)
+        .unwrap_or(DEFAULT_FRAME_RATE);
+
+    Ok(VideoInfo {
+        frame_rate,
+        has_audio: stderr.lines().any(|line| line.contains("Audio:")),
+    })
+}
+
+/// Cache directory of a job, which changes whenever the input file or the pipeline does.
+fn job_dir(input_path: &str, pipeline: &[Operation]) -> Result<PathBuf, CommandError> {
+    let path = std::fs::canonicalize(input_path)?;
+    let metadata = std::fs::metadata(&path)?;
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    format!("{:?}", pipeline).hash(&mut hasher);
+
+    Ok(cache_dir()
+        .join(VIDEO_DIR)
+        .join(format!("{:016x}", hasher.finish())))
+}
+
+fn frame_paths(dir: &Path) -> Result<Vec<PathBuf>, CommandError> {
+    let mut frames: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    frames.sort();
+    Ok(frames)
+}
+
+/// Moves a processed frame into the frame sequence, converting anything but PNG (e.g. OpenEXR for
+/// float results) to 16-bit PNG so ffmpeg reads one consistent sequence.
+fn store_frame(output: &Path, destination: &Path) -> Result<(), CommandError> {
+    if output.extension().is_some_and(|ext| ext == "png") {
+        return Ok(std::fs::rename(output, destination)?);
+    }
+
+    let image = image::open(output)?;
+    let image = if image.color().has_alpha() {
+        image::DynamicImage::ImageRgba16(image.to_rgba16())
+    } else {
+        image::DynamicImage::ImageRgb16(image.to_rgb16())
+    };
+    Ok(image.save(destination)?)
+}
+
+/// Encodes the processed frames at `processed_dir` with the audio of `input_path`. The audio is
+/// re-encoded for both containers, as the input's codec may not be allowed in them.
+fn encode_command(
+    ffmpeg: &Path,
+    video: &VideoInfo,
+    processed_dir: &Path,
+    input_path: &str,
+    has_alpha: bool,
+    output_path: &Path,
+) -> Command {
+    let mut command = ffmpeg_command(ffmpeg);
+    command
+        .args(["-loglevel", "error", "-framerate"])
+        .arg(video.frame_rate.to_string())
+        .arg("-i")
+        .arg(processed_dir.join("%08d.png"))
+        .args(["-i", input_path, "-map", "0:v"]);
+    if video.has_audio {
+        command.args(["-map", "1:a"]);
+    }
+    if has_alpha {
+        command.args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuva420p", "-b:v", "0"]);
+        command.args(["-crf", "30", "-c:a", "libopus"]);
+    } else {
+        // yuv420p needs even dimensions
+        command.args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"]);
+        command.args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"]);
+        command.args(["-c:a", "aac", "-b:a", "192k"]);
+    }
+    command.arg(output_path);
+    command
+}
+
+/// Runs `pipeline` on every frame of the video at `input_path` and writes the result into
+/// `output_dir`, returning the output path.
+///
+/// Results with transparency (e.g. background removal) are written as VP9 WebM with alpha,
+/// everything else as H.264 MP4.
+pub(crate) fn process_video(
+    input_path: &str,
+    output_dir: &str,
+    pipeline: &[Operation],
+    progress: &ProgressFn,
+    cancel: &CancelToken,
+) -> Result<String, CommandError> {
+    if pipeline.is_empty() {
+        return Err(CommandError::InvalidArgument(
+            "Pipeline is empty".to_string(),
+        ));
+    }
+    let ffmpeg = find_ffmpeg().ok_or(CommandError::FfmpegNotFound)?;
+    let video = probe(&ffmpeg, input_path)?;
+
+    let job_dir = job_dir(input_path, pipeline)?;
+    let frames_dir = job_dir.join(FRAMES_DIR);
+    let processed_dir = job_dir.join(PROCESSED_DIR);
+
+    if !job_dir.join(EXTRACTED_MARKER).exists() {
+        info!("Extracting frames of {} with {:?}", input_path, ffmpeg);
+        progress(Progress {
+            stage: ProcessingStage::Preprocess,
+            fraction: 0.0,
+        });
+
+        let _ = std::fs::remove_dir_all(&frames_dir);
+        std::fs::create_dir_all(&frames_dir)?;
+        let mut command = ffmpeg_command(&ffmpeg);
+        command
+            .args(["-loglevel", "error", "-i", input_path, "-vsync", "0"])
+            .arg(frames_dir.join("%08d.png"));
+        run_ffmpeg(command, cancel)?;
+        std::fs::write(job_dir.join(EXTRACTED_MARKER), "")?;
+    }
+    std::fs::create_dir_all(&processed_dir)?;
+
+    let frames = frame_paths(&frames_dir)?;
+    if frames.is_empty() {
+        return Err(CommandError::Processing(format!(
+            "No frames could be extracted from {}",
+            input_path
+        )));
+    }
+
+    let total = frames.len() as f32;
+    let frame_progress = |done: f32| EXTRACT_WEIGHT + (1.0 - EXTRACT_WEIGHT - ENCODE_WEIGHT) * done;
+    let mut resumed = 0;
+    for (index, frame) in frames.iter().enumerate() {
+        let processed = processed_dir.join(frame.file_name().unwrap());
+        if processed.exists() {
+            resumed += 1;
+            continue;
+        }
+        cancel.check()?;
+
+        // Process into a scratch directory and move the result in place, so a frame that was
+        // being written when the job stopped is never mistaken for a finished one
+        let scratch = job_dir.join(format!("scratch_{}", index));
+        std::fs::create_dir_all(&scratch)?;
+        let output = with_intermediate_format(|| {
+            run_pipeline_with_progress(
+                pipeline,
+                frame.to_str().unwrap(),
+                scratch.to_str().unwrap(),
+                &|step| {
+                    progress(Progress {
+                        stage: step.stage,
+                        fraction: frame_progress((index as f32 + step.fraction) / total),
+                    })
+                },
+            )
+        });
+        let moved = output.and_then(|output| store_frame(Path::new(&output), &processed));
+        let _ = std::fs::remove_dir_all(&scratch);
+        moved?;
+    }
+    if resumed > 0 {
+        info!("Resumed {} after {} cached frames", input_path, resumed);
+    }
+
+    cancel.check()?;
+    progress(Progress {
+        stage: ProcessingStage::Postprocess,
+        fraction: 1.0 - ENCODE_WEIGHT,
+    });
+
+    let first = image::open(processed_dir.join(frames[0].file_name().unwrap()))?;
+    let has_alpha = first.color().has_alpha();
+
+    let stem = Path::new(input_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let output_path = Path::new(output_dir).join(format!(
+        "{}_processed.{}",
+        stem,
+        if has_alpha { "webm" } else { "mp4" }
+    ));
+
+    let command = encode_command(
+        &ffmpeg,
+        &video,
+        &processed_dir,
+        input_path,
+        has_alpha,
+        &output_path,
+    );
+    run_ffmpeg(command, cancel)?;
+
+    let _ = std::fs::remove_dir_all(&job_dir);
+    progress(Progress {
+        stage: ProcessingStage::Postprocess,
+        fraction: 1.0,
+    });
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn encode_args(has_audio: bool, has_alpha: bool) -> Vec<String> {
+        let video = VideoInfo {
+            frame_rate: 25.0,
+            has_audio,
+        };
+        args(&encode_command(
+            Path::new("ffmpeg"),
+            &video,
+            Path::new("processed"),
+            "input.mov",
+            has_alpha,
+            Path::new("output.mp4"),
+        ))
+    }
+
+    fn has_pair(args: &[String], flag: &str, value: &str) -> bool {
+        args.windows(2)
+            .any(|pair| pair[0] == flag && pair[1] == value)
+    }
+
+    #[test]
+    fn mp4_output_transcodes_audio_to_aac() {
+        let args = encode_args(true, false);
+        assert!(has_pair(&args, "-map", "1:a"));
+        assert!(has_pair(&args, "-c:v", "libx264"));
+        assert!(has_pair(&args, "-c:a", "aac"));
+        assert!(has_pair(&args, "-framerate", "25"));
+        assert_eq!(args.last().unwrap(), "output.mp4");
+    }
+
+    #[test]
+    fn webm_output_keeps_alpha_and_uses_opus() {
+        let args = encode_args(true, true);
+        assert!(has_pair(&args, "-pix_fmt", "yuva420p"));
+        assert!(has_pair(&args, "-c:a", "libopus"));
+    }
+
+    #[test]
+    fn videos_without_audio_map_no_audio_stream() {
+        let args = encode_args(false, false);
+        assert!(!has_pair(&args, "-map", "1:a"));
+        assert!(has_pair(&args, "-map", "0:v"));
+    }
+
+    #[test]
+    fn job_dir_changes_with_the_input_and_the_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("clip.mp4");
+        std::fs::write(&input, b"frames").unwrap();
+        let input = input.to_str().unwrap();
+
+        let upscale = job_dir(input, &[Operation::Upscale]).unwrap();
+        assert_eq!(job_dir(input, &[Operation::Upscale]).unwrap(), upscale);
+        assert_ne!(job_dir(input, &[Operation::Denoise]).unwrap(), upscale);
+        assert_ne!(
+            job_dir(input, &[Operation::Upscale, Operation::Denoise]).unwrap(),
+            upscale
+        );
+
+        std::fs::write(input, b"other frames").unwrap();
+        assert_ne!(job_dir(input, &[Operation::Upscale]).unwrap(), upscale);
+    }
+
+    #[test]
+    fn job_dir_of_a_missing_input_is_an_error() {
+        assert!(job_dir("missing.mp4", &[Operation::Upscale]).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tracing::{info, warn};
+
+use crate::error::CommandError;
+use crate::image::decode::is_supported;
+use crate::operation::{run_pipeline, Operation};
+use crate::utils::{db_dir, load_json_config, save_json_config};
+use crate::worker::run_blocking;
+
+const WATCH_CONFIG_FILE: &str = "watch_folders.json";
+const WATCH_HISTORY_FILE: &str = "watch_history.jsonl";
+
+/// How long a file has to stay untouched with an unchanged size before it is picked up.
+const SETTLE_DURATION: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static WATCH_MANAGER: OnceLock<WatchManager> = OnceLock::new();
+
+/// A folder whose new images are run through `pipeline` and written to `output_dir`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub id: String,
+    pub input_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub pipeline: Vec<Operation>,
+}
+
+/// The outcome of processing one file picked up by a watch folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchRecord {
+    pub folder_id: String,
+    pub input_path: String,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    pub processed_at: String,
+}
+
+struct PendingFile {
+    last_event: Instant,
+    last_size: Option<u64>,
+}
+
+pub struct WatchManager {
+    app: AppHandle,
+    folders: Mutex<Vec<WatchFolder>>,
+    watcher: Mutex<RecommendedWatcher>,
+}
+
+/// Starts the watch-folder subsystem and resumes every saved watch folder.
+///
+/// The watcher threads are independent of any window, so folders keep being processed while
+/// the main window is hidden to the tray.
+pub fn init(app: AppHandle) -> Result<(), CommandError> {
+    let (events_tx, events_rx) = mpsc::channel::<PathBuf>();
+    let (jobs_tx, jobs_rx) = mpsc::channel::<(WatchFolder, PathBuf)>();
+
+    let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths.into_iter().filter(|p| is_candidate(p)) {
+                    let _ = events_tx.send(path);
+                }
+            }
+        }
+        Err(e) => warn!("Watch error: {}", e),
+    })
+    .map_err(watch_error)?;
+
+    let manager = WatchManager {
+        app,
+        folders: Mutex::new(Vec::new()),
+        watcher: Mutex::new(watcher),
+    };
+    if WATCH_MANAGER.set(manager).is_err() {
+        return Err(CommandError::Internal(
+            "Watch folders are already initialized".to_string(),
+        ));
+    }
+    let manager = manager_ref()?;
+
+    thread::Builder::new()
+        .name("watch-debounce".to_string())
+        .spawn(move || debounce_loop(events_rx, jobs_tx))?;
+    thread::Builder::new()
+        .name("watch-process".to_string())
+        .spawn(move || process_loop(jobs_rx))?;
+
+    for folder in load_config() {
+        if let Err(e) = manager.start(folder.clone()) {
+            warn!(
+                "Failed to resume watch folder {}: {}",
+                folder.input_dir.display(),
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the canonical form of `output_dir` and creates it, rejecting folders whose outputs
+/// would land in the canonical `input_dir` and be processed again. The check runs before anything
+/// is created, so a rejected folder leaves nothing behind.
+fn resolve_output_dir(input_dir: &Path, output_dir: &Path) -> Result<PathBuf, CommandError> {
+    // Canonicalize the nearest existing parent; the missing rest has no links to resolve
+    let existing = output_dir
+        .ancestors()
+        .find(|path| path.as_os_str().is_empty() || path.exists())
+        .unwrap_or(Path::new(""));
+    let mut resolved = if existing.as_os_str().is_empty() {
+        std::env::current_dir()?
+    } else {
+        existing.canonicalize()?
+    };
+    for component in output_dir
+        .strip_prefix(existing)
+        .unwrap_or(output_dir)
+        .components()
+    {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+
+    if resolved.starts_with(input_dir) {
+        return Err(CommandError::InvalidArgument(
+            "Output folder must be outside the watched folder".to_string(),
+        ));
+    }
+    std::fs::create_dir_all(&resolved)?;
+    Ok(resolved)
+}
+
+pub fn manager_ref() -> Result<&'static WatchManager, CommandError> {
+    WATCH_MANAGER
+        .get()
+        .ok_or_else(|| CommandError::Internal("Watch folders are not initialized".to_string()))
+}
+
+fn watch_error(error: notify::Error) -> CommandError {
+    CommandError::Io(error.to_string())
+}
+
+impl WatchManager {
+    pub fn folders(&self) -> Vec<WatchFolder> {
+        self.folders.lock().unwrap().clone()
+    }
+
+    pub fn add(
+        &self,
+        input_dir: PathBuf,
+        output_dir: PathBuf,
+        pipeline: Vec<Operation>,
+    ) -> Result<WatchFolder, CommandError> {
+        if pipeline.is_empty() {
+            return Err(CommandError::InvalidArgument(
+                "Pipeline is empty".to_string(),
+            ));
+        }
+        if !input_dir.is_dir() {
+            return Err(CommandError::InvalidArgument(format!(
+                "{} is not a directory",
+                input_dir.display()
+            )));
+        }
+        // Event paths are reported in canonical form on some platforms (e.g. /private on macOS)
+        let input_dir = input_dir.canonicalize()?;
+        if self
+            .folders()
+            .iter()
+            .any(|folder| folder.input_dir == input_dir)
+        {
+            return Err(CommandError::InvalidArgument(format!(
+                "{} is already watched",
+                input_dir.display()
+            )));
+        }
+        let output_dir = resolve_output_dir(&input_dir, &output_dir)?;
+
+        let folder = WatchFolder {
+            id: uuid::Uuid::new_v4().to_string(),
+            input_dir,
+            output_dir,
+            pipeline,
+        };
+        self.start(folder.clone())?;
+        save_config(&self.folders())?;
+
+        Ok(folder)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), CommandError> {
+        let folder = {
+            let mut folders = self.folders.lock().unwrap();
+            let index = folders
+                .iter()
+                .position(|folder| folder.id == id)
+                .ok_or_else(|| {
+                    CommandError::InvalidArgument(format!("Unknown watch folder: {}", id))
+                })?;
+            folders.remove(index)
+        };
+
+        self.watcher
+            .lock()
+            .unwrap()
+            .unwatch(&folder.input_dir)
+            .map_err(watch_error)?;
+        info!("Stopped watching {}", folder.input_dir.display());
+
+        save_config(&self.folders())
+    }
+
+    fn start(&self, folder: WatchFolder) -> Result<(), CommandError> {
+        self.watcher
+            .lock()
+            .unwrap()
+            .watch(&folder.input_dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        info!("Watching {}", folder.input_dir.display());
+
+        self.folders.lock().unwrap().push(folder);
+        Ok(())
+    }
+
+    fn folder_for(&self, path: &Path) -> Option<WatchFolder> {
+        let parent = path.parent()?;
+        self.folders
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|folder| folder.input_dir == parent)
+            .cloned()
+    }
+}
+
+/// Collects file events and forwards each file once it has stopped changing.
+fn debounce_loop(events: Receiver<PathBuf>, jobs: Sender<(WatchFolder, PathBuf)>) {
+    let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
+    let mut last_poll = Instant::now();
+
+    loop {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(path) => {
+                let last_size = std::fs::metadata(&path).ok().map(|m| m.len());
+                pending.insert(
+                    path,
+                    PendingFile {
+                        last_event: Instant::now(),
+                        last_size,
+                    },
+                );
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if last_poll.elapsed() < POLL_INTERVAL {
+            continue;
+        }
+        last_poll = Instant::now();
+
+        let mut settled = Vec::new();
+        pending.retain(|path, file| {
+            if file.last_event.elapsed() < SETTLE_DURATION {
+                return true;
+            }
+
+            match std::fs::metadata(path).map(|m| m.len()) {
+                // The file disappeared, e.g. a temporary file that got renamed
+                Err(_) => false,
+                Ok(size) if size > 0 && Some(size) == file.last_size => {
+                    settled.push(path.clone());
+                    false
+                }
+                Ok(size) => {
+                    file.last_size = Some(size);
+                    file.last_event = Instant::now();
+                    true
+                }
+            }
+        });
+
+        let Ok(manager) = manager_ref() else {
+            continue;
+        };
+        for path in settled {
+            if let Some(folder) = manager.folder_for(&path) {
+                if jobs.send((folder, path)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Runs settled files through their folder's pipeline, one at a time.
+fn process_loop(jobs: Receiver<(WatchFolder, PathBuf)>) {
+    for (folder, path) in jobs {
+        let input_path = path.to_string_lossy().into_owned();
+        info!("Processing watched file: {}", input_path);
+
+        // Go through the shared worker pool so watched files queue fairly with UI requests
+        let job = {
+            let pipeline = folder.pipeline.clone();
+            let input_path = input_path.clone();
+            let output_dir = folder.output_dir.to_string_lossy().into_owned();
+            move || run_pipeline(&pipeline, &input_path, &output_dir)
+        };
+        let result = tauri::async_runtime::block_on(run_blocking(job)).and_then(|r| r);
+        let record = WatchRecord {
+            folder_id: folder.id,
+            input_path,
+            output_path: result.as_ref().ok().cloned(),
+            error: result.err().map(|e| e.to_string()),
+            processed_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        if let Some(error) = &record.error {
+            warn!("Failed to process {}: {}", record.input_path, error);
+        }
+        if let Err(e) = append_history(&record) {
+            warn!("Failed to record watch history: {}", e);
+        }
+        if let Ok(manager) = manager_ref() {
+            let _ = manager.app.emit("watch-processed", &record);
+        }
+    }
+}
+
+fn is_candidate(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.') || name.starts_with('~'));
+    !hidden && path.to_str().is_some_and(is_supported)
+}
+
+fn load_config() -> Vec<WatchFolder> {
+    load_json_config(WATCH_CONFIG_FILE).unwrap_or_default()
+}
+
+fn save_config(folders: &[WatchFolder]) -> Result<(), CommandError> {
+    save_json_config(WATCH_CONFIG_FILE, folders)
+}
+
+fn append_history(record: &WatchRecord) -> Result<(), CommandError> {
+    let dir = db_dir();
+    std::fs::create_dir_all(&dir)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(WATCH_HISTORY_FILE))?;
+    let line = serde_json::to_string(record).map_err(|e| CommandError::Io(e.to_string()))?;
+    Ok(writeln!(file, "{}", line)?)
+}
+
+/// Returns up to `limit` history records, newest first.
+pub fn read_history(limit: usize) -> Result<Vec<WatchRecord>, CommandError> {
+    let path = db_dir().join(WATCH_HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_folder_must_be_outside_the_watched_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().canonicalize().unwrap();
+
+        for output_dir in [
+            dir.path().to_path_buf(),
+            dir.path().join("."),
+            dir.path().join("nested"),
+            dir.path().join("nested/..").join("deeper"),
+        ] {
+            assert!(
+                resolve_output_dir(&input_dir, &output_dir).is_err(),
+                "{} was accepted",
+                output_dir.display()
+            );
+        }
+        assert!(!dir.path().join("nested").exists());
+        assert!(!dir.path().join("deeper").exists());
+    }
+
+    #[test]
+    fn picks_up_every_supported_format() {
+        for name in ["a.png", "b.GIF", "c.exr", "d.tiff"] {
+            assert!(is_candidate(Path::new(name)), "{} was ignored", name);
+        }
+        for name in [".hidden.png", "~lock.jpg", "notes.txt", "no_extension"] {
+            assert!(!is_candidate(Path::new(name)), "{} was picked up", name);
+        }
+        assert_eq!(is_candidate(Path::new("e.heic")), cfg!(feature = "heic"));
+        assert_eq!(is_candidate(Path::new("f.cr2")), cfg!(feature = "raw"));
+    }
+
+    #[test]
+    fn output_folder_is_created_and_canonicalized() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir(&input_dir).unwrap();
+        let input_dir = input_dir.canonicalize().unwrap();
+
+        let output_dir =
+            resolve_output_dir(&input_dir, &dir.path().join("input/../output")).unwrap();
+        assert!(output_dir.is_dir());
+        assert_eq!(
+            output_dir,
+            dir.path().canonicalize().unwrap().join("output")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::error::CommandError;
+use crate::image::session::session_config;
+use crate::utils::{load_json_config, save_json_config};
+
+const WORKER_CONFIG_FILE: &str = "worker.json";
+
+static WORKER_POOL: OnceLock<WorkerPool> = OnceLock::new();
+/// Ids of jobs holding a [`CancelToken`], and those of them asked to stop.
+static ACTIVE_JOBS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static CANCELLED_JOBS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Bounded pool for CPU-bound work (decoding, inference, encoding).
+///
+/// Jobs wait in FIFO order for a permit and then run on tokio's blocking threads, so the async
+/// workers stay free and concurrent requests are served in arrival order instead of all
+/// contending for the CPU at once.
+pub struct WorkerPool {
+    semaphore: Arc<Semaphore>,
+    limit: Mutex<usize>,
+    running: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerConfig {
+    max_concurrent_jobs: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WorkerStatus {
+    pub limit: usize,
+    pub running: usize,
+    pub queued: usize,
+}
+
+pub fn pool() -> &'static WorkerPool {
+    WORKER_POOL.get_or_init(|| WorkerPool::new(load_limit()))
+}
+
+fn load_limit() -> usize {
+    load_json_config::<WorkerConfig>(WORKER_CONFIG_FILE)
+        .map(|config| config.max_concurrent_jobs)
+        .filter(|&limit| limit > 0)
+        .unwrap_or_else(default_limit)
+}
+
+fn default_limit() -> usize {
+    // Every job runs one session inference at a time, using `intra_threads` threads
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (cores / session_config().intra_threads.max(1)).max(1)
+}
+
+fn save_limit(limit: usize) -> Result<(), CommandError> {
+    save_json_config(
+        WORKER_CONFIG_FILE,
+        &WorkerConfig {
+            max_concurrent_jobs: limit,
+        },
+    )
+}
+
+/// Runs `job` on the shared worker pool once a slot is free.
+pub async fn run_blocking<F, T>(job: F) -> Result<T, CommandError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    pool().run(job).await
+}
+
+impl WorkerPool {
+    fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit: Mutex::new(limit),
+            running: Arc::new(AtomicUsize::new(0)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub async fn run<F, T>(&self, job: F) -> Result<T, CommandError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let queued = CounterGuard::new(&self.queued);
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| CommandError::Internal(e.to_string()))?;
+        drop(queued);
+
+        // The slot is held by the job itself, so it stays taken until the job finishes even if
+        // this future is dropped first
+        let running = CounterGuard::new(&self.running);
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let _running = running;
+            job()
+        })
+        .await
+        .map_err(|e| CommandError::Internal(e.to_string()))
+    }
+
+    /// Changes how many jobs may run at once. Shrinking waits for running jobs to release their
+    /// slots; queued jobs keep their order.
+    pub fn set_limit(&self, limit: usize) -> Result<(), CommandError> {
+        if limit == 0 {
+            return Err(CommandError::InvalidArgument(
+                "Concurrency limit must be at least 1".to_string(),
+            ));
+        }
+
+        let mut current = self.limit.lock().unwrap();
+        if limit > *current {
+            self.semaphore.add_permits(limit - *current);
+        } else if limit < *current {
+            let surplus = (*current - limit) as u32;
+            let semaphore = self.semaphore.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many_owned(surplus).await {
+                    permits.forget();
+                }
+            });
+        }
+
+        info!("Worker pool limit changed from {} to {}", *current, limit);
+        *current = limit;
+        if let Err(e) = save_limit(limit) {
+            warn!("Failed to save worker settings: {}", e);
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        WorkerStatus {
+            limit: *self.limit.lock().unwrap(),
+            running: self.running.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Requests cancellation of the job with the given id. Jobs check for it between steps through
+/// their [`CancelToken`]; returns whether such a job is running.
+pub fn cancel_job(job_id: &str) -> bool {
+    let mut cancelled = CANCELLED_JOBS.lock().unwrap();
+    if cancelled.contains(job_id) {
+        return true;
+    }
+    let running = ACTIVE_JOBS.lock().unwrap().contains(job_id);
+    if running {
+        info!("Cancelling job {}", job_id);
+        cancelled.insert(job_id.to_string());
+    }
+    running
+}
+
+/// Lets a long-running job notice that it has been cancelled. Jobs without an id can't be
+/// cancelled.
+pub struct CancelToken {
+    job_id: Option<String>,
+}
+
+impl CancelToken {
+    pub fn new(job_id: Option<String>) -> Self {
+        if let Some(job_id) = &job_id {
+            ACTIVE_JOBS.lock().unwrap().insert(job_id.clone());
+        }
+        Self { job_id }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.job_id
+            .as_ref()
+            .is_some_and(|job_id| CANCELLED_JOBS.lock().unwrap().contains(job_id))
+    }
+
+    /// Returns an error if the job has been cancelled, for use with `?` between steps.
+    pub fn check(&self) -> Result<(), CommandError> {
+        if self.is_cancelled() {
+            Err(CommandError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for CancelToken {
+    fn drop(&mut self) {
+        if let Some(job_id) = &self.job_id {
+            CANCELLED_JOBS.lock().unwrap().remove(job_id);
+            ACTIVE_JOBS.lock().unwrap().remove(job_id);
+        }
+    }
+}
+
+/// Keeps a counter incremented for as long as it is alive, including when the awaiting future
+/// is dropped early (e.g. an HTTP client disconnecting).
+struct CounterGuard(Arc<AtomicUsize>);
+
+impl CounterGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for CounterGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dropped_callers_keep_the_slot_until_the_job_ends() {
+        let pool: &'static WorkerPool = Box::leak(Box::new(WorkerPool::new(1)));
+        let (started_tx, started_rx) = mpsc::channel();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+
+        let caller = tokio::spawn(pool.run(move || {
+            started_tx.send(()).unwrap();
+            finish_rx.recv().unwrap();
+        }));
+        tokio::task::spawn_blocking(move || started_rx.recv().unwrap())
+            .await
+            .unwrap();
+        caller.abort();
+        let _ = caller.await;
+
+        assert_eq!(pool.semaphore.available_permits(), 0);
+        assert_eq!(pool.status().running, 1);
+
+        finish_tx.send(()).unwrap();
+        pool.run(|| ()).await.unwrap();
+        assert_eq!(pool.status().running, 0);
+    }
+}
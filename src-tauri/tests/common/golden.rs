@@ -0,0 +1,84 @@
+//! Golden-image checks: results are compared with reference images in `tests/golden` through the
+//! quality metrics, so small numeric differences between platforms pass but regressions fail.
+//!
+//! After an intended change in output, run the tests with `UPDATE_GOLDEN=1` to rewrite the
+//! references, and review the new images before committing them.
+
+use image::DynamicImage;
+use imagenie_lib::image::metrics;
+use std::path::PathBuf;
+
+/// Lowest acceptable similarity to the reference.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    pub min_psnr: f64,
+    pub min_ssim: f64,
+    pub min_alpha_iou: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            min_psnr: 40.0,
+            min_ssim: 0.98,
+            min_alpha_iou: 0.98,
+        }
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+/// Asserts that `output` matches the reference image `name` within `tolerance`.
+pub fn assert_golden(name: &str, output: &DynamicImage, tolerance: Tolerance) {
+    let path = golden_path(name);
+    // References are 8-bit, like the PNGs users get
+    let output = if output.color().has_alpha() {
+        DynamicImage::ImageRgba8(output.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(output.to_rgb8())
+    };
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        output.save(&path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&path).unwrap_or_else(|e| {
+        panic!(
+            "Golden image {} could not be read ({}); run with UPDATE_GOLDEN=1 to create it",
+            path.display(),
+            e
+        )
+    });
+    let metrics = metrics::compare(&reference, &output).unwrap();
+
+    let psnr = metrics.psnr.unwrap_or(f64::INFINITY);
+    assert!(
+        psnr >= tolerance.min_psnr,
+        "{}: PSNR {:.2} dB is below {} dB",
+        name,
+        psnr,
+        tolerance.min_psnr
+    );
+    assert!(
+        metrics.ssim >= tolerance.min_ssim,
+        "{}: SSIM {:.4} is below {}",
+        name,
+        metrics.ssim,
+        tolerance.min_ssim
+    );
+    if let Some(alpha_iou) = metrics.alpha_iou {
+        assert!(
+            alpha_iou >= tolerance.min_alpha_iou,
+            "{}: alpha IoU {:.4} is below {}",
+            name,
+            alpha_iou,
+            tolerance.min_alpha_iou
+        );
+    }
+}
@@ -0,0 +1,63 @@
+#![allow(dead_code)]
+
+pub mod golden;
+pub mod onnx;
+
+use image::{DynamicImage, RgbImage};
+use imagenie_lib::image::model::ImageModel;
+use imagenie_lib::image::processor::ModelProcessor;
+use std::path::Path;
+
+/// Writes `model` into `dir` and returns its path.
+pub fn write_model(dir: &Path, name: &str, model: &[u8]) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, model).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Saves `image` into `dir` as PNG and returns its path.
+pub fn write_image(dir: &Path, name: &str, image: &DynamicImage) -> String {
+    let path = dir.join(name);
+    image.save(&path).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Runs `image` through model `M` loaded from `graph`, both written to a temporary directory.
+pub fn run_model<M: ImageModel + Send + Sync>(
+    graph: &[u8],
+    image: &DynamicImage,
+    params: &M::Params,
+) -> DynamicImage {
+    let dir = tempfile::tempdir().unwrap();
+    let model = write_model(dir.path(), "model.onnx", graph);
+    let processor = ModelProcessor::<M>::new(&model).unwrap();
+    let path = write_image(dir.path(), "input.png", image);
+
+    processor.process_single(&path, params).unwrap()
+}
+
+/// A reproducible noise image, so failures can be replayed.
+pub fn noise_image(width: u32, height: u32, seed: u32) -> RgbImage {
+    let mut state = seed.wrapping_mul(0x9E37_79B9).max(1);
+    RgbImage::from_fn(width, height, |_, _| {
+        let mut next = || {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        };
+        image::Rgb([next(), next(), next()])
+    })
+}
+
+/// Gradients with a bright disc in the middle, as a fixture with both smooth areas and edges.
+pub fn pattern_image(width: u32, height: u32) -> RgbImage {
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let radius = width.min(height) as f32 / 3.0;
+    RgbImage::from_fn(width, height, |x, y| {
+        let distance = (x as f32 - center_x).hypot(y as f32 - center_y);
+        let disc = if distance < radius { 255 } else { 32 };
+        image::Rgb([(x * 255 / width) as u8, (y * 255 / height) as u8, disc])
+    })
+}
@@ -0,0 +1,281 @@
+//! Minimal ONNX encoder for building tiny models in tests, so they run without downloading real
+//! models. Only the protobuf fields the test graphs use are written.
+
+/// `TensorProto.DataType` values.
+pub const FLOAT: i64 = 1;
+pub const FLOAT16: i64 = 10;
+
+const OPSET_VERSION: i64 = 13;
+const IR_VERSION: i64 = 8;
+
+fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn put_int(buffer: &mut Vec<u8>, field: u32, value: i64) {
+    put_varint(buffer, (field as u64) << 3);
+    put_varint(buffer, value as u64);
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_varint(buffer, (field as u64) << 3 | 2);
+    put_varint(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+/// A graph input or output. `None` dimensions are dynamic.
+pub struct ValueInfo {
+    pub name: &'static str,
+    pub elem_type: i64,
+    pub dims: Vec<Option<i64>>,
+}
+
+impl ValueInfo {
+    fn encode(&self) -> Vec<u8> {
+        let mut shape = Vec::new();
+        for (index, dim) in self.dims.iter().enumerate() {
+            let mut dimension = Vec::new();
+            match dim {
+                Some(value) => put_int(&mut dimension, 1, *value),
+                None => {
+                    let name = format!("{}_{}", self.name, index);
+                    put_bytes(&mut dimension, 2, name.as_bytes())
+                }
+            }
+            put_bytes(&mut shape, 1, &dimension);
+        }
+
+        let mut tensor_type = Vec::new();
+        put_int(&mut tensor_type, 1, self.elem_type);
+        put_bytes(&mut tensor_type, 2, &shape);
+        let mut type_proto = Vec::new();
+        put_bytes(&mut type_proto, 1, &tensor_type);
+
+        let mut info = Vec::new();
+        put_bytes(&mut info, 1, self.name.as_bytes());
+        put_bytes(&mut info, 2, &type_proto);
+        info
+    }
+}
+
+pub enum Attribute {
+    Int(i64),
+    Ints(Vec<i64>),
+    String(&'static str),
+}
+
+pub struct Node {
+    pub op_type: &'static str,
+    pub inputs: Vec<&'static str>,
+    pub outputs: Vec<&'static str>,
+    pub attributes: Vec<(&'static str, Attribute)>,
+}
+
+impl Node {
+    pub fn new(op_type: &'static str, inputs: &[&'static str], outputs: &[&'static str]) -> Self {
+        Self {
+            op_type,
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn attribute(mut self, name: &'static str, value: Attribute) -> Self {
+        self.attributes.push((name, value));
+        self
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut node = Vec::new();
+        for input in &self.inputs {
+            put_bytes(&mut node, 1, input.as_bytes());
+        }
+        for output in &self.outputs {
+            put_bytes(&mut node, 2, output.as_bytes());
+        }
+        put_bytes(&mut node, 4, self.op_type.as_bytes());
+
+        for (name, value) in &self.attributes {
+            let mut attribute = Vec::new();
+            put_bytes(&mut attribute, 1, name.as_bytes());
+            // `AttributeProto.AttributeType`: INT = 2, STRING = 3, INTS = 7
+            match value {
+                Attribute::Int(value) => {
+                    put_int(&mut attribute, 3, *value);
+                    put_int(&mut attribute, 20, 2);
+                }
+                Attribute::String(value) => {
+                    put_bytes(&mut attribute, 4, value.as_bytes());
+                    put_int(&mut attribute, 20, 3);
+                }
+                Attribute::Ints(values) => {
+                    for value in values {
+                        put_int(&mut attribute, 8, *value);
+                    }
+                    put_int(&mut attribute, 20, 7);
+                }
+            }
+            put_bytes(&mut node, 5, &attribute);
+        }
+        node
+    }
+}
+
+/// A constant `f32` tensor.
+pub struct Initializer {
+    pub name: &'static str,
+    pub dims: Vec<i64>,
+    pub values: Vec<f32>,
+}
+
+impl Initializer {
+    fn encode(&self) -> Vec<u8> {
+        let mut tensor = Vec::new();
+        for dim in &self.dims {
+            put_int(&mut tensor, 1, *dim);
+        }
+        put_int(&mut tensor, 2, FLOAT);
+        put_bytes(&mut tensor, 8, self.name.as_bytes());
+        let raw: Vec<u8> = self.values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        put_bytes(&mut tensor, 9, &raw);
+        tensor
+    }
+}
+
+#[derive(Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub initializers: Vec<Initializer>,
+    pub inputs: Vec<ValueInfo>,
+    pub outputs: Vec<ValueInfo>,
+}
+
+impl Graph {
+    /// Serializes the graph as a complete `ModelProto`.
+    pub fn to_model(&self) -> Vec<u8> {
+        let mut graph = Vec::new();
+        for node in &self.nodes {
+            put_bytes(&mut graph, 1, &node.encode());
+        }
+        put_bytes(&mut graph, 2, b"test");
+        for initializer in &self.initializers {
+            put_bytes(&mut graph, 5, &initializer.encode());
+        }
+        for input in &self.inputs {
+            put_bytes(&mut graph, 11, &input.encode());
+        }
+        for output in &self.outputs {
+            put_bytes(&mut graph, 12, &output.encode());
+        }
+
+        let mut opset = Vec::new();
+        put_int(&mut opset, 2, OPSET_VERSION);
+
+        let mut model = Vec::new();
+        put_int(&mut model, 1, IR_VERSION);
+        put_bytes(&mut model, 2, b"imagenie-tests");
+        put_bytes(&mut model, 7, &graph);
+        put_bytes(&mut model, 8, &opset);
+        model
+    }
+}
+
+fn image_tensor(name: &'static str, elem_type: i64, channels: i64) -> ValueInfo {
+    ValueInfo {
+        name,
+        elem_type,
+        dims: vec![Some(1), Some(channels), None, None],
+    }
+}
+
+/// Passes the `[1, 3, H, W]` input through unchanged.
+pub fn identity(elem_type: i64) -> Vec<u8> {
+    Graph {
+        nodes: vec![Node::new("Identity", &["input"], &["output"])],
+        inputs: vec![image_tensor("input", elem_type, 3)],
+        outputs: vec![image_tensor("output", elem_type, 3)],
+        ..Default::default()
+    }
+    .to_model()
+}
+
+/// Doubles the size of an `f16` image by repeating every pixel, like a 2x upscaler.
+pub fn nearest_upscale_2x() -> Vec<u8> {
+    Graph {
+        // Resize in f32, as not every execution provider resizes f16
+        nodes: vec![
+            Node::new("Cast", &["input"], &["input_f32"]).attribute("to", Attribute::Int(FLOAT)),
+            Node::new("Resize", &["input_f32", "", "scales"], &["resized"])
+                .attribute("mode", Attribute::String("nearest"))
+                .attribute(
+                    "coordinate_transformation_mode",
+                    Attribute::String("asymmetric"),
+                )
+                .attribute("nearest_mode", Attribute::String("floor")),
+            Node::new("Cast", &["resized"], &["output"]).attribute("to", Attribute::Int(FLOAT16)),
+        ],
+        initializers: vec![Initializer {
+            name: "scales",
+            dims: vec![4],
+            values: vec![1.0, 1.0, 2.0, 2.0],
+        }],
+        inputs: vec![image_tensor("input", FLOAT16, 3)],
+        outputs: vec![image_tensor("output", FLOAT16, 3)],
+    }
+    .to_model()
+}
+
+/// Outputs a `[1, 1, H, W]` mask filled with `value`, whatever the input.
+pub fn constant_mask(value: f32) -> Vec<u8> {
+    Graph {
+        nodes: vec![
+            Node::new("ReduceMean", &["input"], &["mean"])
+                .attribute("axes", Attribute::Ints(vec![1]))
+                .attribute("keepdims", Attribute::Int(1)),
+            Node::new("Mul", &["mean", "zero"], &["zeros"]),
+            Node::new("Add", &["zeros", "value"], &["output"]),
+        ],
+        initializers: vec![
+            Initializer {
+                name: "zero",
+                dims: vec![],
+                values: vec![0.0],
+            },
+            Initializer {
+                name: "value",
+                dims: vec![],
+                values: vec![value],
+            },
+        ],
+        inputs: vec![image_tensor("input", FLOAT, 3)],
+        outputs: vec![image_tensor("output", FLOAT, 1)],
+    }
+    .to_model()
+}
+
+/// Outputs a `[1, 1, H, W]` mask of the mean brightness of an input normalized to `[-1, 1]`, so
+/// bright areas become foreground.
+pub fn brightness_mask() -> Vec<u8> {
+    Graph {
+        nodes: vec![
+            Node::new("ReduceMean", &["input"], &["mean"])
+                .attribute("axes", Attribute::Ints(vec![1]))
+                .attribute("keepdims", Attribute::Int(1)),
+            Node::new("Mul", &["mean", "half"], &["scaled"]),
+            Node::new("Add", &["scaled", "half"], &["output"]),
+        ],
+        initializers: vec![Initializer {
+            name: "half",
+            dims: vec![],
+            values: vec![0.5],
+        }],
+        inputs: vec![image_tensor("input", FLOAT, 3)],
+        outputs: vec![image_tensor("output", FLOAT, 1)],
+    }
+    .to_model()
+}
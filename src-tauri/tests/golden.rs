@@ -0,0 +1,54 @@
+//! Golden-image regression tests of the full model pipelines, see `common::golden`.
+
+mod common;
+
+use image::DynamicImage;
+use imagenie_lib::image::model::{BackgroundRemovalModel, FaceRestorationModel, UpscalingModel};
+use imagenie_lib::image::types::{BackgroundRemovalParams, FaceRestorationParams, UpscalingParams};
+
+use common::golden::{assert_golden, Tolerance};
+use common::{onnx, pattern_image, run_model};
+
+const SIZE: u32 = 32;
+
+fn pattern() -> DynamicImage {
+    DynamicImage::ImageRgb8(pattern_image(SIZE, SIZE))
+}
+
+#[test]
+fn upscaling() {
+    let output = run_model::<UpscalingModel>(
+        &onnx::nearest_upscale_2x(),
+        &pattern(),
+        &UpscalingParams::default(),
+    );
+
+    assert_golden("upscaling", &output, Tolerance::default());
+}
+
+#[test]
+fn face_restoration() {
+    let params = FaceRestorationParams {
+        model_width: SIZE as usize,
+        model_height: SIZE as usize,
+        ..Default::default()
+    };
+
+    let output =
+        run_model::<FaceRestorationModel>(&onnx::identity(onnx::FLOAT), &pattern(), &params);
+
+    assert_golden("face_restoration", &output, Tolerance::default());
+}
+
+#[test]
+fn background_removal() {
+    let params = BackgroundRemovalParams {
+        model_width: SIZE as usize,
+        model_height: SIZE as usize,
+        ..Default::default()
+    };
+
+    let output = run_model::<BackgroundRemovalModel>(&onnx::brightness_mask(), &pattern(), &params);
+
+    assert_golden("background_removal", &output, Tolerance::default());
+}
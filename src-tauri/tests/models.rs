@@ -0,0 +1,235 @@
+//! End-to-end runs of the models on CPU, using tiny synthetic ONNX graphs in place of the real
+//! weights.
+
+mod common;
+
+use image::{DynamicImage, GenericImageView};
+use imagenie_lib::image::model::{
+    BackgroundRemovalModel, FaceRestorationModel, ImageModel, InpaintingModel, UpscalingModel,
+};
+use imagenie_lib::image::processor::{ModelProcessor, ProcessingStage, Progress};
+use imagenie_lib::image::types::{
+    BackgroundRemovalParams, FaceRestorationParams, InpaintingParams, MaskSource, UpscalingParams,
+};
+use imagenie_lib::image::validate::ValidationError;
+use imagenie_lib::image::ImageProcessingError;
+use std::sync::Mutex;
+
+use common::{noise_image, onnx, run_model, write_image, write_model};
+
+fn upscaler(dir: &std::path::Path) -> ModelProcessor<UpscalingModel> {
+    let model = write_model(dir, "upscale.onnx", &onnx::nearest_upscale_2x());
+    ModelProcessor::new(&model).unwrap()
+}
+
+#[test]
+fn upscaling_repeats_every_pixel() {
+    let input = noise_image(6, 4, 1);
+
+    let output = run_model::<UpscalingModel>(
+        &onnx::nearest_upscale_2x(),
+        &DynamicImage::ImageRgb8(input.clone()),
+        &UpscalingParams::default(),
+    )
+    .to_rgb8();
+
+    assert_eq!(output.dimensions(), (12, 8));
+    for (x, y, pixel) in output.enumerate_pixels() {
+        let expected = input.get_pixel(x / 2, y / 2);
+        for c in 0..3 {
+            assert!(
+                pixel[c].abs_diff(expected[c]) <= 1,
+                "pixel ({}, {}) is {:?}, expected {:?}",
+                x,
+                y,
+                pixel,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn face_restoration_identity_keeps_the_image() {
+    let input = noise_image(16, 16, 2);
+
+    let params = FaceRestorationParams {
+        model_width: 16,
+        model_height: 16,
+        ..Default::default()
+    };
+    let output = run_model::<FaceRestorationModel>(
+        &onnx::identity(onnx::FLOAT),
+        &DynamicImage::ImageRgb8(input.clone()),
+        &params,
+    )
+    .to_rgb8();
+
+    assert_eq!(output.dimensions(), input.dimensions());
+    for (actual, expected) in output.pixels().zip(input.pixels()) {
+        for c in 0..3 {
+            assert!(actual[c].abs_diff(expected[c]) <= 1);
+        }
+    }
+}
+
+#[test]
+fn background_removal_turns_the_mask_into_alpha() {
+    let params = BackgroundRemovalParams {
+        model_width: 8,
+        model_height: 8,
+        ..Default::default()
+    };
+    let output = run_model::<BackgroundRemovalModel>(
+        &onnx::constant_mask(0.75),
+        &DynamicImage::ImageRgb8(noise_image(10, 6, 3)),
+        &params,
+    );
+
+    assert_eq!(output.dimensions(), (8, 8));
+    let expected = (0.75 * 255.0) as u8;
+    assert!(output.to_rgba8().pixels().all(|pixel| pixel[3] == expected));
+}
+
+#[test]
+fn progress_goes_through_every_stage() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_image(
+        dir.path(),
+        "input.png",
+        &DynamicImage::ImageRgb8(noise_image(4, 4, 4)),
+    );
+    let reports = Mutex::new(Vec::new());
+
+    upscaler(dir.path())
+        .process_single_with_progress(&path, &UpscalingParams::default(), &|progress: Progress| {
+            reports.lock().unwrap().push(progress)
+        })
+        .unwrap();
+
+    let reports = reports.into_inner().unwrap();
+    let stages: Vec<ProcessingStage> = reports.iter().map(|report| report.stage).collect();
+    assert_eq!(
+        stages,
+        [
+            ProcessingStage::Preprocess,
+            ProcessingStage::Inference,
+            ProcessingStage::Postprocess,
+            ProcessingStage::Postprocess,
+        ]
+    );
+    assert!(reports
+        .windows(2)
+        .all(|pair| pair[0].fraction <= pair[1].fraction));
+    assert_eq!(reports.last().unwrap().fraction, 1.0);
+}
+
+#[test]
+fn batch_processes_every_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<String> = (0..3)
+        .map(|index| {
+            write_image(
+                dir.path(),
+                &format!("input{}.png", index),
+                &DynamicImage::ImageRgb8(noise_image(2 + 2 * index, 2, index)),
+            )
+        })
+        .collect();
+    let completed = Mutex::new(Vec::new());
+
+    let outputs = upscaler(dir.path())
+        .process_batch_with_progress(paths, &UpscalingParams::default(), &|progress| {
+            completed.lock().unwrap().push(progress.fraction)
+        })
+        .unwrap();
+
+    // Results keep the order of the inputs even though they run in parallel
+    let widths: Vec<u32> = outputs.iter().map(|image| image.width()).collect();
+    assert_eq!(widths, [4, 8, 12]);
+    let mut completed = completed.into_inner().unwrap();
+    completed.sort_by(f32::total_cmp);
+    assert_eq!(completed, [1.0 / 3.0, 2.0 / 3.0, 1.0]);
+}
+
+#[test]
+fn missing_input_fails_validation() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.png");
+
+    let result =
+        upscaler(dir.path()).process_single(missing.to_str().unwrap(), &UpscalingParams::default());
+
+    assert!(matches!(
+        result,
+        Err(ImageProcessingError::Validation(
+            ValidationError::NotFound { .. }
+        ))
+    ));
+}
+
+#[test]
+fn invalid_model_fails_to_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = write_model(dir.path(), "broken.onnx", b"not a model");
+
+    assert!(ModelProcessor::<UpscalingModel>::new(&model).is_err());
+}
+
+#[test]
+fn inpainting_keeps_unmasked_pixels_with_their_alpha_and_depth() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = image::ImageBuffer::<image::Rgba<u16>, _>::from_fn(6, 4, |x, y| {
+        image::Rgba([
+            x as u16 * 10_000,
+            y as u16 * 15_000,
+            1234,
+            30_000 + x as u16,
+        ])
+    });
+    let path = write_image(
+        dir.path(),
+        "input.png",
+        &DynamicImage::ImageRgba16(input.clone()),
+    );
+    let mut mask = std::io::Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(image::GrayImage::new(6, 4))
+        .write_to(&mut mask, image::ImageFormat::Png)
+        .unwrap();
+
+    let mut params = InpaintingParams {
+        model_width: 8,
+        model_height: 8,
+        mask: Some(MaskSource::Bytes(mask.into_inner())),
+        ..Default::default()
+    };
+    InpaintingModel::preprocess(&path, &mut params).unwrap();
+    let output =
+        InpaintingModel::postprocess(&ndarray::Array4::zeros((1, 3, 8, 8)), &params).unwrap();
+
+    assert_eq!(output, DynamicImage::ImageRgba16(input));
+}
+
+#[test]
+fn inpainting_masks_are_validated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_image(
+        dir.path(),
+        "input.png",
+        &DynamicImage::ImageRgb8(noise_image(4, 4, 5)),
+    );
+    let missing = dir.path().join("missing.png");
+
+    let mut params = InpaintingParams {
+        mask: Some(MaskSource::Path(missing.to_str().unwrap().to_string())),
+        ..Default::default()
+    };
+    let result = InpaintingModel::preprocess(&path, &mut params);
+
+    assert!(matches!(
+        result,
+        Err(ImageProcessingError::Validation(
+            ValidationError::NotFound { .. }
+        ))
+    ));
+}
@@ -12,3 +12,26 @@ export const GITHUB_REPO = {
 export const FACE_RESTORATION_MODEL = 'face_restoration.onnx';
 export const IMAGE_UPSCALING_MODEL = 'image_upscaling.onnx';
 export const BACKGROUND_REMOVAL_MODEL = 'background_removal.onnx';
+export const DENOISE_MODEL = 'denoise.onnx';
+export const COLORIZATION_MODEL = 'colorization.onnx';
+export const INPAINTING_MODEL = 'inpainting.onnx';
+export const ARTIFACT_REMOVAL_MODEL = 'artifact_removal.onnx';
+export const ENHANCE_MODEL = 'low_light_enhancement.onnx';
+export const SEGMENTATION_MODEL = 'segmentation.onnx';
+export const PROMPT_ENCODER_MODEL = 'sam_encoder.onnx';
+export const PROMPT_DECODER_MODEL = 'sam_decoder.onnx';
+
+// Every model the app uses, checked and downloaded on startup
+export const MODELS = [
+  BACKGROUND_REMOVAL_MODEL,
+  FACE_RESTORATION_MODEL,
+  IMAGE_UPSCALING_MODEL,
+  DENOISE_MODEL,
+  COLORIZATION_MODEL,
+  INPAINTING_MODEL,
+  ARTIFACT_REMOVAL_MODEL,
+  ENHANCE_MODEL,
+  SEGMENTATION_MODEL,
+  PROMPT_ENCODER_MODEL,
+  PROMPT_DECODER_MODEL,
+];
@@ -50,7 +50,30 @@ const messages = {
       processingCompleted: 'Processing Completed',
       processingCompletedDesc: 'Processing completed, please compare the results',
       dimensionError: 'Image dimension error',
-      dimensionErrorDesc: 'The image dimension is too large, please resize it to {maxDimension}px or less'
+      dimensionErrorDesc: 'The image dimension is too large, please resize it to {maxDimension}px or less',
+      processingError: 'Processing failed',
+      metrics: 'PSNR {psnr} dB, SSIM {ssim} against the input',
+      metricsError: 'Could not compare the result with the input'
+    },
+    errors: {
+      file_not_found: '{path} does not exist',
+      unsupported_format: '{path} is not an image in a supported format',
+      corrupt_image: '{path} could not be read',
+      file_too_large: 'The file is {bytes} MB, more than the limit of {max_bytes} MB',
+      too_many_pixels: 'The image is {width}x{height} pixels, more than the limit of {max_pixels} pixels',
+      insufficient_memory: 'Processing needs about {required_bytes} MB of memory, more than the limit of {max_bytes} MB',
+      model_not_found: 'The {model} model was not found, please download it again',
+      model_load_failed: 'The {model} model could not be loaded',
+      inference_failed: 'The model failed to process the image',
+      image_error: 'The image could not be read or written',
+      io_error: 'A file could not be read or written',
+      download_failed: 'Downloading the {model} model failed',
+      cancelled: 'Processing was cancelled',
+      ffmpeg_not_found: 'ffmpeg was not found, please install it to process videos',
+      ffmpeg_failed: 'ffmpeg could not process the video',
+      invalid_argument: 'Invalid input',
+      processing_failed: 'Processing failed',
+      internal_error: 'Something went wrong inside the app'
     },
     initialization: {
       title: 'Initialization for the first time',
@@ -115,7 +138,30 @@ const messages = {
       processingCompleted: '处理完成',
       processingCompletedDesc: '处理完成，请对比查看结果',
       dimensionError: '图片尺寸错误',
-      dimensionErrorDesc: '图片尺寸过大，请将图片缩放到{maxDimension}px或以下'
+      dimensionErrorDesc: '图片尺寸过大，请将图片缩放到{maxDimension}px或以下',
+      processingError: '处理失败',
+      metrics: '与原图相比：PSNR {psnr} dB，SSIM {ssim}',
+      metricsError: '无法将结果与原图进行比较'
+    },
+    errors: {
+      file_not_found: '{path} 不存在',
+      unsupported_format: '{path} 不是支持的图片格式',
+      corrupt_image: '无法读取 {path}',
+      file_too_large: '文件大小为 {bytes} MB，超过了 {max_bytes} MB 的限制',
+      too_many_pixels: '图片为 {width}x{height} 像素，超过了 {max_pixels} 像素的限制',
+      insufficient_memory: '处理大约需要 {required_bytes} MB 内存，超过了 {max_bytes} MB 的限制',
+      model_not_found: '未找到 {model} 模型，请重新下载',
+      model_load_failed: '无法加载 {model} 模型',
+      inference_failed: '模型处理图片失败',
+      image_error: '无法读取或写入图片',
+      io_error: '无法读取或写入文件',
+      download_failed: '下载 {model} 模型失败',
+      cancelled: '处理已取消',
+      ffmpeg_not_found: '未找到 ffmpeg，请安装后再处理视频',
+      ffmpeg_failed: 'ffmpeg 无法处理该视频',
+      invalid_argument: '输入无效',
+      processing_failed: '处理失败',
+      internal_error: '应用内部出错'
     },
     sidebar: {
       title: 'Imagenie',
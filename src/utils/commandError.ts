@@ -0,0 +1,37 @@
+import { i18n } from '../i18n';
+
+/** Error returned by every backend command. */
+export interface CommandError {
+  code: string;
+  message: string;
+  details: Record<string, unknown>;
+}
+
+export function isCommandError(error: unknown): error is CommandError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error;
+}
+
+const MEGABYTE = 1024 * 1024;
+
+/** Byte counts are shown in MB, as in the backend's own messages. */
+function formatDetails(details: Record<string, unknown>): Record<string, unknown> {
+  return Object.fromEntries(
+    Object.entries(details ?? {}).map(([key, value]) =>
+      key.endsWith('bytes') && typeof value === 'number'
+        ? [key, Math.floor(value / MEGABYTE)]
+        : [key, value]
+    )
+  );
+}
+
+/** Localized text for an error thrown by `invoke`, falling back to the backend's message. */
+export function errorMessage(error: unknown): string {
+  if (!isCommandError(error)) {
+    return String(error);
+  }
+  const key = `errors.${error.code}`;
+  if (!i18n.global.te(key)) {
+    return error.message;
+  }
+  return i18n.global.t(key, formatDetails(error.details));
+}